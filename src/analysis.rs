@@ -0,0 +1,793 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use unicode_normalization::char::is_combining_mark;
+use unicode_normalization::UnicodeNormalization;
+
+use crate::language::Language;
+use crate::lexer::{Lexer, Recognizers, Spanned, Token};
+use crate::snowball::stem::stem;
+use crate::snowball::StemmingAlgorithm;
+
+/// Turns raw text into the terms that get indexed and searched.
+///
+/// Text is split into tokens by the [`Tokenizer`], then each token goes
+/// through the [`TokenFilter`]s in order, any of which may drop it. The
+/// analyzer is stored with the model, so indexing and querying always go
+/// through exactly the same chain. The default chain lowercases and stems.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(from = "SavedAnalyzer")]
+pub struct Analyzer {
+    /// What the chain is called, to tell models' analyzers apart
+    pub name: String,
+
+    /// How text is split into tokens
+    pub tokenizer: Tokenizer,
+
+    /// What happens to each token, in order
+    pub filters: Vec<TokenFilter>,
+
+    /// The stemmer [`TokenFilter::Stem`] uses, if any
+    pub stemmer: Option<StemmingAlgorithm>,
+
+    /// Stem each document with the stemmer of its detected language,
+    /// falling back to `stemmer` when the language can't be told
+    pub detect_language: bool,
+}
+
+/// Splits text into the tokens that become terms. Settings left out of a
+/// saved tokenizer take their default.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Tokenizer {
+    /// Which structured tokens, like versions, dates and URLs, are indexed
+    /// whole
+    pub recognizers: Recognizers,
+
+    /// Also index the parts of `camelCase` and `snake_case` identifiers,
+    /// rather than only splitting on underscores
+    pub split_identifiers: bool,
+}
+
+/// One step of an analyzer's chain, which changes a token's text or drops
+/// the token
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum TokenFilter {
+    /// NFKC normalisation and lowercasing, so `Ｒｕｓｔ` and `RUST` are both
+    /// `rust`. Typographic apostrophes become `'`, so `don’t` is `don't`.
+    Lowercase,
+    /// Removes diacritics, so `café` and `cafe` are the same term
+    StripAccents,
+    /// Drops tokens that are one of `words`, as they are at this point in
+    /// the chain
+    StopWords { words: BTreeSet<String> },
+    /// Replaces tokens found in `words` with the word they map to, so
+    /// `js` and `javascript` can be the same term
+    Synonyms { words: BTreeMap<String, String> },
+    /// Drops tokens with fewer than `min` or more than `max` characters
+    Length { min: usize, max: usize },
+    /// Stems words and numbers with the analyzer's stemmer, or the stemmer
+    /// for the document's language. CJK text and structured tokens like
+    /// versions and URLs are left as they are, so they are searched exactly.
+    Stem,
+}
+
+impl Default for Analyzer {
+    fn default() -> Self {
+        Self {
+            name: "default".to_string(),
+            tokenizer: Tokenizer::default(),
+            filters: vec![TokenFilter::Lowercase, TokenFilter::Stem],
+            stemmer: default_stemmer(),
+            detect_language: false,
+        }
+    }
+}
+
+impl Default for Tokenizer {
+    fn default() -> Self {
+        Self {
+            recognizers: Recognizers::ALL,
+            split_identifiers: true,
+        }
+    }
+}
+
+fn default_stemmer() -> Option<StemmingAlgorithm> {
+    Some(StemmingAlgorithm::Porter2)
+}
+
+fn no_recognizers() -> Recognizers {
+    Recognizers::NONE
+}
+
+/// An analyzer as saved with a model, or written by hand for `init
+/// --analyzer`. Models saved before analyzers were chains of filters have
+/// their settings in place of the tokenizer and filters, which are turned
+/// into the chain they stand for. A chain without a tokenizer gets the
+/// default one.
+#[derive(Deserialize)]
+struct SavedAnalyzer {
+    #[serde(default)]
+    name: Option<String>,
+    #[serde(default)]
+    tokenizer: Option<Tokenizer>,
+    #[serde(default)]
+    filters: Option<Vec<TokenFilter>>,
+    /// Models saved before this was configurable were always stemmed with
+    /// Porter2
+    #[serde(default = "default_stemmer")]
+    stemmer: Option<StemmingAlgorithm>,
+    #[serde(default)]
+    detect_language: bool,
+
+    #[serde(default)]
+    strip_accents: bool,
+    #[serde(default)]
+    stop_words: BTreeSet<String>,
+    /// Models saved before this existed only split on underscores
+    #[serde(default)]
+    split_identifiers: bool,
+    /// Models saved before these existed recognise none of them
+    #[serde(default = "no_recognizers")]
+    recognizers: Recognizers,
+}
+
+impl From<SavedAnalyzer> for Analyzer {
+    fn from(saved: SavedAnalyzer) -> Self {
+        let tokenizer = match (saved.tokenizer, &saved.filters) {
+            (Some(tokenizer), _) => tokenizer,
+            (None, Some(_)) => Tokenizer::default(),
+            (None, None) => Tokenizer {
+                recognizers: saved.recognizers,
+                split_identifiers: saved.split_identifiers,
+            },
+        };
+
+        let filters = saved.filters.unwrap_or_else(|| {
+            let mut filters = vec![TokenFilter::Lowercase];
+            if saved.strip_accents {
+                filters.push(TokenFilter::StripAccents);
+            }
+            if !saved.stop_words.is_empty() {
+                filters.push(TokenFilter::StopWords {
+                    words: saved.stop_words,
+                });
+            }
+            filters.push(TokenFilter::Stem);
+            filters
+        });
+
+        Self {
+            name: saved.name.unwrap_or_else(|| "default".to_string()),
+            tokenizer,
+            filters,
+            stemmer: saved.stemmer,
+            detect_language: saved.detect_language,
+        }
+    }
+}
+
+impl Analyzer {
+    /// Analyses text into terms, each with its position. Tokens a filter
+    /// drops, like stop words, keep their position, so the terms either side
+    /// of them are as far apart as they were in the text.
+    pub fn analyze<'a>(&'a self, text: &'a str) -> impl Iterator<Item = (u32, String)> + 'a {
+        self.analyze_with(text, self.stemmer)
+    }
+
+    /// Analyses text with a stemmer other than the configured one
+    pub fn analyze_with<'a>(
+        &'a self,
+        text: &'a str,
+        stemmer: Option<StemmingAlgorithm>,
+    ) -> impl Iterator<Item = (u32, String)> + 'a {
+        self.filter(self.tokenizer.tokenize(text), stemmer)
+    }
+
+    /// Analyses a query's text, which is tokenized as in
+    /// [`Tokenizer::tokenize_query`]
+    pub fn analyze_query<'a>(
+        &'a self,
+        text: &'a str,
+        stemmer: Option<StemmingAlgorithm>,
+    ) -> impl Iterator<Item = (u32, String)> + 'a {
+        self.filter(self.tokenizer.tokenize_query(text), stemmer)
+    }
+
+    /// Runs tokens through the filters, keeping the positions of those left
+    fn filter<'a>(
+        &'a self,
+        tokens: impl Iterator<Item = (u32, Spanned<Token<'a>>)> + 'a,
+        stemmer: Option<StemmingAlgorithm>,
+    ) -> impl Iterator<Item = (u32, String)> + 'a {
+        tokens.filter_map(move |(position, tok)| Some((position, self.term(tok.value, stemmer)?)))
+    }
+
+    /// Analyses text, pairing each term with the byte range, line and
+    /// column it came from. The parts of a split identifier fall inside the
+    /// range of the whole identifier.
+    pub fn analyze_spans<'a>(
+        &'a self,
+        text: &'a str,
+        stemmer: Option<StemmingAlgorithm>,
+    ) -> impl Iterator<Item = Spanned<String>> + 'a {
+        self.tokenizer.tokenize(text).filter_map(move |(_, tok)| {
+            let term = self.term(tok.value, stemmer)?;
+            Some(tok.map(|_| term))
+        })
+    }
+
+    /// Runs a single token through the filters, unless one drops it
+    fn term(&self, tok: Token, stemmer: Option<StemmingAlgorithm>) -> Option<String> {
+        filter_text(&self.filters, tok, stemmer)
+    }
+
+    /// Every stemmer a document may have been indexed with, so queries can
+    /// be analysed to match documents in any language
+    pub fn stemmers(&self) -> Vec<Option<StemmingAlgorithm>> {
+        let mut stemmers = vec![self.stemmer];
+        if self.detect_language {
+            for language in Language::ALL {
+                let stemmer = Some(language.stemmer());
+                if !stemmers.contains(&stemmer) {
+                    stemmers.push(stemmer);
+                }
+            }
+        }
+
+        stemmers
+    }
+
+    /// Adds words to the chain's stop words, changed by the filters before
+    /// them the same way as text. Without a stop word filter, one is added
+    /// before stemming.
+    pub fn with_stop_words<I, S>(mut self, words: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let position = self
+            .filters
+            .iter()
+            .position(|f| matches!(f, TokenFilter::StopWords { .. }))
+            .unwrap_or_else(|| {
+                let stem = self.filters.iter().position(|f| *f == TokenFilter::Stem);
+                let position = stem.unwrap_or(self.filters.len());
+                let words = BTreeSet::new();
+                self.filters
+                    .insert(position, TokenFilter::StopWords { words });
+                position
+            });
+
+        let before = &self.filters[..position];
+        let words: Vec<String> = words
+            .into_iter()
+            .filter_map(|w| filter_text(before, Token::Word(w.as_ref()), None))
+            .collect();
+        if let TokenFilter::StopWords { words: stop_words } = &mut self.filters[position] {
+            stop_words.extend(words);
+        }
+        self
+    }
+
+    /// Applies the chain's lowercasing and accent stripping to text
+    pub fn normalize(&self, text: &str) -> String {
+        self.filters
+            .iter()
+            .filter(|f| matches!(f, TokenFilter::Lowercase | TokenFilter::StripAccents))
+            .fold(text.to_string(), |text, filter| {
+                filter
+                    .apply(text, Token::Word(""), None)
+                    .unwrap_or_default()
+            })
+    }
+}
+
+/// Runs a token's text through `filters`, unless one drops it
+fn filter_text(
+    filters: &[TokenFilter],
+    tok: Token,
+    stemmer: Option<StemmingAlgorithm>,
+) -> Option<String> {
+    let text = match tok {
+        Token::Symbol(_) => return None,
+        tok => tok.text()?.to_string(),
+    };
+
+    filters
+        .iter()
+        .try_fold(text, |text, filter| filter.apply(text, tok, stemmer))
+}
+
+impl TokenFilter {
+    /// Filters the current text of `tok`, returning `None` to drop it
+    fn apply(
+        &self,
+        text: String,
+        tok: Token,
+        stemmer: Option<StemmingAlgorithm>,
+    ) -> Option<String> {
+        match self {
+            TokenFilter::Lowercase => Some(
+                text.nfkc()
+                    .map(|c| if c == '\u{2019}' { '\'' } else { c })
+                    .collect::<String>()
+                    .to_lowercase(),
+            ),
+            TokenFilter::StripAccents => Some(
+                text.nfd()
+                    .filter(|c| !is_combining_mark(*c))
+                    .nfc()
+                    .collect(),
+            ),
+            TokenFilter::StopWords { words } => (!words.contains(&text)).then_some(text),
+            TokenFilter::Synonyms { words } => Some(words.get(&text).cloned().unwrap_or(text)),
+            TokenFilter::Length { min, max } => (*min..=*max)
+                .contains(&text.chars().count())
+                .then_some(text),
+            TokenFilter::Stem => match (stemmer, tok) {
+                (Some(alg), Token::Word(_)) => stem(Token::Word(&text), alg).map(String::from),
+                (Some(alg), Token::Number(_)) => stem(Token::Number(&text), alg).map(String::from),
+                _ => Some(text),
+            },
+        }
+    }
+}
+
+impl Tokenizer {
+    /// Splits text into tokens with their positions and where they are in
+    /// the text. Identifiers are followed by their parts, and URLs, emails
+    /// and hashtags by the words inside them, all at the same position, so
+    /// they can be searched for whole or by any of their words. CJK text
+    /// becomes characters and pairs of them.
+    pub fn tokenize<'a>(
+        &'a self,
+        text: &'a str,
+    ) -> impl Iterator<Item = (u32, Spanned<Token<'a>>)> + 'a {
+        self.tokenize_as(text, false)
+    }
+
+    /// Splits a query into tokens like [`Tokenizer::tokenize`], except that
+    /// identifiers are only their parts, and URLs, emails and hashtags only
+    /// themselves. `parseQuery` then finds the parts together at one
+    /// position, so it matches `parse_query` too.
+    pub fn tokenize_query<'a>(
+        &'a self,
+        text: &'a str,
+    ) -> impl Iterator<Item = (u32, Spanned<Token<'a>>)> + 'a {
+        self.tokenize_as(text, true)
+    }
+
+    fn tokenize_as<'a>(
+        &'a self,
+        text: &'a str,
+        query: bool,
+    ) -> impl Iterator<Item = (u32, Spanned<Token<'a>>)> + 'a {
+        let lexer = Lexer::with_recognizers(text, self.recognizers);
+        let mut next = 0;
+        lexer.spanned().flat_map(move |tok| {
+            let parts = self.tokens(tok.value, query);
+            let position = next;
+            next += width(&parts);
+
+            parts.into_iter().filter_map(move |(offset, part)| {
+                let word = part.text()?;
+
+                // Parts are slices of the token, so their offset is how far in they start
+                let token = &text[tok.span.clone()];
+                let start = word.as_ptr() as usize - token.as_ptr() as usize;
+                let spanned = Spanned {
+                    value: part,
+                    span: tok.span.start + start..tok.span.start + start + word.len(),
+                    line: tok.line,
+                    column: tok.column + token[..start].chars().count(),
+                };
+                Some((position + offset, spanned))
+            })
+        })
+    }
+
+    /// The tokens a lexer token is indexed as, with their positions from
+    /// the token's
+    fn tokens<'a>(&self, tok: Token<'a>, query: bool) -> Vec<(u32, Token<'a>)> {
+        match tok {
+            Token::Word(word) => self.words(word, query),
+            Token::Cjk(run) => (0..).zip(cjk_grams(run)).collect(),
+            // Queries look for the whole token, which is where its words are
+            Token::Url(_) | Token::Email(_) | Token::Hashtag(_) if query => vec![(0, tok)],
+            Token::Url(text) | Token::Email(text) | Token::Hashtag(text) => {
+                let inner = Lexer::with_recognizers(text, Recognizers::NONE);
+                let words = inner.flat_map(|tok| self.tokens(tok, query));
+                let mut tokens = vec![(0, tok)];
+                tokens.extend(words.map(|(_, word)| (0, word)));
+                tokens
+            }
+            Token::Symbol(_) | Token::EOF | Token::Error => Vec::new(),
+            _ => vec![(0, tok)],
+        }
+    }
+
+    /// The words a word token is indexed as: the word itself, followed by
+    /// its parts at the same position if it is an identifier made of
+    /// several. Queries only look for the parts.
+    fn words<'a>(&self, word: &'a str, query: bool) -> Vec<(u32, Token<'a>)> {
+        if !self.split_identifiers {
+            let parts = word.split('_').filter(|part| !part.is_empty());
+            return (0..).zip(parts.map(Token::Word)).collect();
+        }
+
+        let parts = identifier_parts(word);
+        if parts == [word] {
+            return vec![(0, Token::Word(word))];
+        }
+
+        let whole = (!query).then_some(word);
+        whole
+            .into_iter()
+            .chain(parts)
+            .map(|word| (0, Token::Word(word)))
+            .collect()
+    }
+}
+
+/// How many positions tokens take up, given their positions from the first
+fn width(tokens: &[(u32, Token)]) -> u32 {
+    tokens
+        .iter()
+        .map(|(offset, _)| offset + 1)
+        .max()
+        .unwrap_or(0)
+}
+
+/// Splits an identifier on underscores and case changes, so `parse_query`,
+/// `parseQuery` and `ParseQuery` all give `parse` and `query`. A run of
+/// capitals is kept together as an acronym: `HTTPRequest` gives `HTTP` and
+/// `Request`.
+fn identifier_parts(word: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+
+    for piece in word.split('_').filter(|piece| !piece.is_empty()) {
+        let chars: Vec<(usize, char)> = piece.char_indices().collect();
+        let mut start = 0;
+
+        for i in 1..chars.len() {
+            let (offset, c) = chars[i];
+            let prev = chars[i - 1].1;
+            let next = chars.get(i + 1).map(|&(_, n)| n);
+
+            let boundary = c.is_uppercase()
+                && (prev.is_lowercase()
+                    || prev.is_numeric()
+                    || (prev.is_uppercase() && next.is_some_and(char::is_lowercase)));
+            if boundary {
+                parts.push(&piece[start..offset]);
+                start = offset;
+            }
+        }
+        parts.push(&piece[start..]);
+    }
+
+    parts
+}
+
+/// Splits a run of CJK text, which has no spaces to find words by, into
+/// each character followed by the pair it starts: `東京都` gives `東`, `東京`,
+/// `京`, `京都` and `都`. Any run of characters then analyses to consecutive
+/// terms wherever it appears, so searching for it as a phrase finds it.
+fn cjk_grams(run: &str) -> Vec<Token<'_>> {
+    let starts: Vec<usize> = run
+        .char_indices()
+        .filter(|&(_, c)| !is_combining_mark(c))
+        .map(|(i, _)| i)
+        .chain([run.len()])
+        .collect();
+
+    let mut grams = Vec::new();
+    for (i, window) in starts.windows(2).enumerate() {
+        grams.push(Token::Cjk(&run[window[0]..window[1]]));
+        if let Some(&end) = starts.get(i + 2) {
+            grams.push(Token::Cjk(&run[window[0]..end]));
+        }
+    }
+    grams
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stop_words;
+
+    fn terms(analyzer: &Analyzer, text: &str) -> Vec<String> {
+        analyzer.analyze(text).map(|(_, term)| term).collect()
+    }
+
+    fn positioned(terms: &[(u32, &str)]) -> Vec<(u32, String)> {
+        terms.iter().map(|&(p, t)| (p, t.to_string())).collect()
+    }
+
+    /// Different casings should produce the same term
+    #[test]
+    fn case_folding() {
+        let analyzer = Analyzer::default();
+
+        assert_eq!(terms(&analyzer, "Rust rust RUST"), vec!["rust"; 3]);
+    }
+
+    /// Compatibility forms should be normalised before stemming
+    #[test]
+    fn nfkc() {
+        let analyzer = Analyzer::default();
+
+        // Full-width letters and the "fi" ligature
+        assert_eq!(terms(&analyzer, "Ｒｕｓｔ ﬁle"), vec!["rust", "file"]);
+    }
+
+    /// Stop words should be dropped regardless of case, leaving a gap where
+    /// they were
+    #[test]
+    fn stop_words() {
+        let analyzer = Analyzer::default().with_stop_words(["The", "of"]);
+
+        assert_eq!(
+            analyzer.analyze("the Speed OF light").collect::<Vec<_>>(),
+            positioned(&[(1, "speed"), (3, "light")])
+        );
+
+        // Contractions should match with either apostrophe
+        let english = Analyzer::default().with_stop_words(stop_words::ENGLISH.split_whitespace());
+        assert_eq!(
+            terms(&english, "I'm sure you don’t. I’M SURE"),
+            vec!["sure", "sure"]
+        );
+    }
+
+    /// Each stemmer should produce its own terms, and none keep words whole
+    #[test]
+    fn stemmers() {
+        let with = |stemmer| Analyzer {
+            stemmer,
+            ..Default::default()
+        };
+
+        assert_eq!(terms(&with(None), "generously"), vec!["generously"]);
+        assert_eq!(
+            terms(&with(Some(StemmingAlgorithm::Porter)), "generously"),
+            vec!["gener"]
+        );
+        assert_eq!(
+            terms(&with(Some(StemmingAlgorithm::Porter2)), "generously"),
+            vec!["generous"]
+        );
+        assert_eq!(
+            terms(&with(Some(StemmingAlgorithm::German)), "Häuser"),
+            vec!["haus"]
+        );
+    }
+
+    /// Identifiers should be indexed whole and in parts
+    #[test]
+    fn identifiers() {
+        let analyzer = Analyzer {
+            stemmer: None,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            terms(&analyzer, "parse_query HTTPRequest utf8Decode plain"),
+            vec![
+                "parse_query",
+                "parse",
+                "query",
+                "httprequest",
+                "http",
+                "request",
+                "utf8decode",
+                "utf8",
+                "decode",
+                "plain"
+            ]
+        );
+        assert_eq!(terms(&analyzer, "__init__"), vec!["__init__", "init"]);
+
+        // The parts share the identifier's position, and queries only use them
+        let indexed: Vec<_> = analyzer.analyze("a parseQuery b").collect();
+        let queried: Vec<_> = analyzer.analyze_query("a parseQuery b", None).collect();
+        assert_eq!(
+            indexed,
+            positioned(&[
+                (0, "a"),
+                (1, "parsequery"),
+                (1, "parse"),
+                (1, "query"),
+                (2, "b")
+            ])
+        );
+        assert_eq!(
+            queried,
+            positioned(&[(0, "a"), (1, "parse"), (1, "query"), (2, "b")])
+        );
+
+        // Models from before identifiers were split only break on underscores
+        let mut legacy = analyzer;
+        legacy.tokenizer.split_identifiers = false;
+        assert_eq!(
+            terms(&legacy, "parse_query __init__ HTTPRequest"),
+            vec!["parse", "query", "init", "httprequest"]
+        );
+    }
+
+    /// Each term should know where in the text it came from
+    #[test]
+    fn spans() {
+        let analyzer = Analyzer::default();
+
+        let spans: Vec<_> = analyzer
+            .analyze_spans("Running\nparseQuery", None)
+            .map(|tok| (tok.span, tok.line, tok.column, tok.value))
+            .collect();
+
+        assert_eq!(
+            spans,
+            vec![
+                (0..7, 1, 1, "running".to_string()),
+                (8..18, 2, 1, "parsequery".to_string()),
+                (8..13, 2, 1, "parse".to_string()),
+                (13..18, 2, 6, "query".to_string()),
+            ]
+        );
+    }
+
+    /// Accents should only be removed when asked to
+    #[test]
+    fn accent_stripping() {
+        let keep = Analyzer::default();
+        let strip = Analyzer {
+            filters: vec![TokenFilter::Lowercase, TokenFilter::StripAccents],
+            ..Default::default()
+        };
+
+        assert_eq!(keep.normalize("Café"), "café");
+        assert_eq!(strip.normalize("Café"), "cafe");
+        // Decomposed input should behave the same as precomposed
+        assert_eq!(strip.normalize("Cafe\u{301}"), "cafe");
+    }
+
+    /// CJK text should be indexed as single characters and pairs of them,
+    /// and never stemmed
+    #[test]
+    fn cjk() {
+        let analyzer = Analyzer::default();
+
+        assert_eq!(
+            terms(&analyzer, "東京都に住む"),
+            vec![
+                "東", "東京", "京", "京都", "都", "都に", "に", "に住", "住", "住む", "む"
+            ]
+        );
+        assert_eq!(
+            terms(&analyzer, "Rust语言"),
+            vec!["rust", "语", "语言", "言"]
+        );
+    }
+
+    /// Structured tokens should be indexed whole and unstemmed, and URLs,
+    /// emails and hashtags by their words as well
+    #[test]
+    fn structured_tokens() {
+        let analyzer = Analyzer::default();
+
+        assert_eq!(
+            terms(&analyzer, "Released v1.2.3 on 2024-06-01"),
+            vec!["releas", "v1.2.3", "on", "2024-06-01"]
+        );
+        assert_eq!(
+            terms(&analyzer, "#Running at https://Example.com/docs"),
+            vec![
+                "#running",
+                "run",
+                "at",
+                "https://example.com/docs",
+                "https",
+                "exampl",
+                "com",
+                "doc"
+            ]
+        );
+
+        // The words inside share the token's position, and queries only use the token
+        let indexed: Vec<_> = analyzer.analyze("see a@b.io now").collect();
+        let queried: Vec<_> = analyzer.analyze_query("see a@b.io now", None).collect();
+        assert_eq!(
+            indexed,
+            positioned(&[
+                (0, "see"),
+                (1, "a@b.io"),
+                (1, "a"),
+                (1, "b"),
+                (1, "io"),
+                (2, "now")
+            ])
+        );
+        assert_eq!(
+            queried,
+            positioned(&[(0, "see"), (1, "a@b.io"), (2, "now")])
+        );
+
+        let mut old = Analyzer::default();
+        old.tokenizer.recognizers = Recognizers::NONE;
+        assert_eq!(terms(&old, "v1.2.3"), vec!["v1", "2", "3"]);
+    }
+
+    /// Filters should run in order, so stop words and synonyms are compared
+    /// after lowercasing and before stemming
+    #[test]
+    fn filter_chain() {
+        let analyzer = Analyzer {
+            name: "docs".to_string(),
+            filters: vec![
+                TokenFilter::Lowercase,
+                TokenFilter::Synonyms {
+                    words: [("js", "javascript")]
+                        .map(|(from, to)| (from.to_string(), to.to_string()))
+                        .into(),
+                },
+                TokenFilter::Length { min: 2, max: 12 },
+                TokenFilter::Stem,
+            ],
+            ..Default::default()
+        }
+        .with_stop_words(["The"]);
+
+        assert_eq!(
+            analyzer.filters[3],
+            TokenFilter::StopWords {
+                words: ["the".to_string()].into()
+            }
+        );
+        assert_eq!(
+            terms(&analyzer, "The JS runtimes a internationalization"),
+            vec!["javascript", "runtim"]
+        );
+    }
+
+    /// Analyzers saved before they were chains should load as the chain
+    /// their settings stand for, and chains should load as they were saved
+    #[test]
+    fn serialise() {
+        let legacy: Analyzer = serde_json::from_str(
+            r#"{"strip_accents": true, "stop_words": ["the"], "stemmer": "porter"}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            legacy.filters,
+            vec![
+                TokenFilter::Lowercase,
+                TokenFilter::StripAccents,
+                TokenFilter::StopWords {
+                    words: ["the".to_string()].into()
+                },
+                TokenFilter::Stem,
+            ]
+        );
+        assert_eq!(legacy.tokenizer.recognizers, Recognizers::NONE);
+        assert!(!legacy.tokenizer.split_identifiers);
+
+        // A hand-written chain should get the default tokenizer, or default
+        // settings for what it leaves out
+        let chain: Analyzer =
+            serde_json::from_str(r#"{"filters": [{"type": "lowercase"}]}"#).unwrap();
+        assert_eq!(chain.tokenizer, Tokenizer::default());
+        assert_eq!(chain.filters, vec![TokenFilter::Lowercase]);
+        let chain: Analyzer =
+            serde_json::from_str(r#"{"tokenizer": {"split_identifiers": false}, "filters": []}"#)
+                .unwrap();
+        assert_eq!(chain.tokenizer.recognizers, Recognizers::ALL);
+        assert!(!chain.tokenizer.split_identifiers);
+
+        let analyzer = Analyzer::default().with_stop_words(["a"]);
+        let json = serde_json::to_string(&analyzer).unwrap();
+        assert!(json.contains(r#"{"type":"stop_words","words":["a"]}"#));
+        assert_eq!(serde_json::from_str::<Analyzer>(&json).unwrap(), analyzer);
+    }
+}
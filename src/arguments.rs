@@ -1,6 +1,15 @@
+use std::fs;
+use std::io;
 use std::path::PathBuf;
 
-use clap::{Parser, Subcommand};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+
+use crate::analysis::{Analyzer, TokenFilter};
+use crate::extract::RecordFields;
+use crate::lexer::Recognizers;
+use crate::ranker::{Bm25, Bm25f, Ranker, TfIdf};
+use crate::snowball::StemmingAlgorithm;
+use crate::stop_words;
 
 #[derive(Parser, Debug)]
 #[command(
@@ -19,6 +28,11 @@ pub struct Arguments {
 
 #[derive(Subcommand, Debug)]
 pub enum Commands {
+    /// Create a new, empty model
+    Init {
+        #[command(flatten)]
+        analysis: AnalysisArgs,
+    },
     /// Enter search mode
     Search {
         /// The search query
@@ -27,11 +41,331 @@ pub enum Commands {
         /// Interactive mode flag
         #[arg(short, long, help = "Interactive mode: stays open until you quit")]
         interactive: bool,
+
+        #[command(flatten)]
+        ranking: RankingArgs,
+
+        #[arg(
+            long,
+            value_enum,
+            help = "Fail unless the model was indexed with this stemmer"
+        )]
+        stemmer: Option<Stemmer>,
+
+        #[arg(
+            long,
+            default_value_t = 1,
+            help = "How many matching passages to show with each result, or 0 for none"
+        )]
+        passages: usize,
+
+        #[arg(
+            long,
+            default_value_t = 20,
+            help = "How many of the best results to show"
+        )]
+        limit: usize,
     },
     /// Add a new entry
     Add {
-        #[arg(help = "A path to a file to add to the model")]
+        #[arg(help = "A path to a file or directory to add to the model")]
         path: PathBuf,
+
+        #[arg(
+            long,
+            help = "Only add files matching this glob, relative to the path (repeatable)"
+        )]
+        include: Vec<String>,
+
+        #[arg(
+            long,
+            help = "Skip files and directories matching this glob, relative to the path (repeatable)"
+        )]
+        exclude: Vec<String>,
+
+        #[arg(
+            long,
+            value_enum,
+            help = "Fail unless the model was indexed with this stemmer"
+        )]
+        stemmer: Option<Stemmer>,
+
+        #[command(flatten)]
+        records: RecordArgs,
     },
+    /// Remove entries from the model
+    Remove {
+        #[arg(help = "An indexed path, or a glob matching indexed paths, to remove")]
+        path: String,
+    },
+    /// Re-index changed files and drop deleted ones
+    Sync,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy)]
+pub enum Stemmer {
+    Porter,
+    Porter2,
+    German,
+    French,
+    Spanish,
+    /// Index words as they are
+    None,
+}
+
+impl Stemmer {
+    pub fn algorithm(self) -> Option<StemmingAlgorithm> {
+        match self {
+            Stemmer::Porter => Some(StemmingAlgorithm::Porter),
+            Stemmer::Porter2 => Some(StemmingAlgorithm::Porter2),
+            Stemmer::German => Some(StemmingAlgorithm::German),
+            Stemmer::French => Some(StemmingAlgorithm::French),
+            Stemmer::Spanish => Some(StemmingAlgorithm::Spanish),
+            Stemmer::None => None,
+        }
+    }
+}
+
+/// Structured tokens that can be indexed whole
+#[derive(ValueEnum, Debug, Clone, Copy)]
+pub enum TokenKind {
+    Decimals,
+    Versions,
+    Hex,
+    Dates,
+    Emails,
+    Urls,
+    Hashtags,
+}
+
+impl TokenKind {
+    /// Stops `recognizers` from recognising this kind of token
+    fn disable(self, recognizers: &mut Recognizers) {
+        let enabled = match self {
+            TokenKind::Decimals => &mut recognizers.decimals,
+            TokenKind::Versions => &mut recognizers.versions,
+            TokenKind::Hex => &mut recognizers.hex,
+            TokenKind::Dates => &mut recognizers.dates,
+            TokenKind::Emails => &mut recognizers.emails,
+            TokenKind::Urls => &mut recognizers.urls,
+            TokenKind::Hashtags => &mut recognizers.hashtags,
+        };
+        *enabled = false;
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct AnalysisArgs {
+    #[arg(
+        long,
+        conflicts_with_all = ["strip_accents", "stemmer", "detect_language", "stop_words", "stop_words_file", "no_recognize"],
+        help = "Read the whole analyzer, its tokenizer and chain of filters, from a JSON file"
+    )]
+    pub analyzer: Option<PathBuf>,
+
+    #[arg(long, help = "Remove accents, so \"café\" matches \"cafe\"")]
+    pub strip_accents: bool,
+
+    #[arg(
+        long,
+        value_enum,
+        default_value_t = Stemmer::Porter2,
+        help = "The stemmer used for indexing and every later search"
+    )]
+    pub stemmer: Stemmer,
+
+    #[arg(
+        long,
+        help = "Stem each document for its detected language, using --stemmer when unsure"
+    )]
+    pub detect_language: bool,
+
+    #[arg(
+        long,
+        value_parser = parse_stop_words,
+        help = "Ignore a built-in list of stop words, e.g. english (repeatable)"
+    )]
+    pub stop_words: Vec<&'static str>,
+
+    #[arg(
+        long,
+        help = "Ignore the stop words listed in a file, one or more per line (repeatable)"
+    )]
+    pub stop_words_file: Vec<PathBuf>,
+
+    #[arg(
+        long,
+        value_enum,
+        help = "Split these tokens into words, numbers and symbols instead of indexing them whole (repeatable)"
+    )]
+    pub no_recognize: Vec<TokenKind>,
+}
+
+impl AnalysisArgs {
+    pub fn analyzer(&self) -> io::Result<Analyzer> {
+        if let Some(path) = &self.analyzer {
+            let json = fs::read_to_string(path)?;
+            return serde_json::from_str(&json)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e));
+        }
+
+        let mut analyzer = Analyzer::default();
+        if self.strip_accents {
+            // Right after lowercasing, so stop words and stems see plain letters
+            analyzer.filters.insert(1, TokenFilter::StripAccents);
+        }
+        analyzer.stemmer = self.stemmer.algorithm();
+        analyzer.detect_language = self.detect_language;
+        for kind in &self.no_recognize {
+            kind.disable(&mut analyzer.tokenizer.recognizers);
+        }
 
-}
\ No newline at end of file
+        for list in &self.stop_words {
+            analyzer = analyzer.with_stop_words(list.split_whitespace());
+        }
+        for path in &self.stop_words_file {
+            analyzer = analyzer.with_stop_words(stop_words::from_file(path)?);
+        }
+
+        Ok(analyzer)
+    }
+}
+
+fn parse_stop_words(s: &str) -> Result<&'static str, String> {
+    stop_words::builtin(s).ok_or_else(|| format!("no built-in stop words for `{}`", s))
+}
+
+#[derive(Args, Debug)]
+pub struct RecordArgs {
+    #[arg(
+        long,
+        help = "Index each record of JSON, JSONL and CSV files as its own document"
+    )]
+    pub records: bool,
+
+    #[arg(
+        long,
+        default_value = "id",
+        requires = "records",
+        help = "The field holding each record's id, shown in results"
+    )]
+    pub id_field: String,
+
+    #[arg(
+        long,
+        default_value = "title",
+        requires = "records",
+        help = "The field holding each record's title"
+    )]
+    pub title_field: String,
+
+    #[arg(
+        long,
+        requires = "records",
+        help = "A field making up the body (repeatable), every other field by default"
+    )]
+    pub body_field: Vec<String>,
+}
+
+impl RecordArgs {
+    /// The fields to index records with, if records were asked for
+    pub fn fields(&self) -> Option<RecordFields> {
+        self.records.then(|| RecordFields {
+            id: self.id_field.clone(),
+            title: Some(self.title_field.clone()),
+            body: self.body_field.clone(),
+        })
+    }
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy)]
+pub enum RankerKind {
+    /// Term frequency × inverse document frequency
+    TfIdf,
+    /// Okapi BM25
+    Bm25,
+    /// BM25 with per-field weights
+    Bm25f,
+}
+
+#[derive(Args, Debug)]
+pub struct RankingArgs {
+    #[arg(long, value_enum, default_value_t = RankerKind::Bm25, help = "How to score results")]
+    pub ranker: RankerKind,
+
+    #[arg(
+        long,
+        default_value_t = 1.2,
+        value_parser = parse_k1,
+        help = "BM25 term frequency saturation"
+    )]
+    pub k1: f32,
+
+    #[arg(
+        long,
+        default_value_t = 0.75,
+        value_parser = parse_b,
+        help = "BM25 document length normalisation, from 0 to 1"
+    )]
+    pub b: f32,
+
+    #[arg(
+        long = "weight",
+        value_parser = parse_weight,
+        help = "BM25F field boost as field=weight (repeatable)"
+    )]
+    pub weights: Vec<(String, f32)>,
+}
+
+impl RankingArgs {
+    pub fn ranker(&self) -> Box<dyn Ranker> {
+        match self.ranker {
+            RankerKind::TfIdf => Box::new(TfIdf),
+            RankerKind::Bm25 => Box::new(Bm25 {
+                k1: self.k1,
+                b: self.b,
+            }),
+            RankerKind::Bm25f => Box::new(Bm25f {
+                k1: self.k1,
+                b: self.b,
+                weights: self.weights.iter().cloned().collect(),
+                ..Default::default()
+            }),
+        }
+    }
+}
+
+fn parse_k1(s: &str) -> Result<f32, String> {
+    let k1: f32 = s
+        .parse()
+        .map_err(|e| format!("invalid k1 `{}`: {}", s, e))?;
+    if !k1.is_finite() || k1 < 0.0 {
+        return Err(format!("k1 must be a non-negative number, got `{}`", s));
+    }
+
+    Ok(k1)
+}
+
+fn parse_b(s: &str) -> Result<f32, String> {
+    let b: f32 = s.parse().map_err(|e| format!("invalid b `{}`: {}", s, e))?;
+    // Also rejects NaN
+    if !(0.0..=1.0).contains(&b) {
+        return Err(format!("b must be between 0 and 1, got `{}`", s));
+    }
+
+    Ok(b)
+}
+
+fn parse_weight(s: &str) -> Result<(String, f32), String> {
+    let (field, weight) = s
+        .split_once('=')
+        .ok_or_else(|| format!("expected field=weight, got `{}`", s))?;
+    let weight: f32 = weight
+        .parse()
+        .map_err(|e| format!("invalid weight `{}`: {}", weight, e))?;
+    if !weight.is_finite() {
+        return Err(format!("weight must be a finite number, got `{}`", s));
+    }
+
+    Ok((field.to_string(), weight))
+}
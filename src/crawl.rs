@@ -0,0 +1,140 @@
+use std::path::{Path, PathBuf};
+
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use walkdir::WalkDir;
+
+/// Include/exclude glob filters applied to paths relative to the crawl root
+#[derive(Debug, Default)]
+pub struct Filter {
+    include: Option<GlobSet>,
+    exclude: Option<GlobSet>,
+}
+
+impl Filter {
+    pub fn new(include: &[String], exclude: &[String]) -> Result<Self, globset::Error> {
+        Ok(Self {
+            include: build_set(include)?,
+            exclude: build_set(exclude)?,
+        })
+    }
+
+    /// A file is kept if it matches an include pattern (when any are given)
+    /// and does not match an exclude pattern
+    pub fn is_match(&self, rel: &Path) -> bool {
+        let included = self.include.as_ref().is_none_or(|set| set.is_match(rel));
+        included && !self.is_excluded(rel)
+    }
+
+    fn is_excluded(&self, rel: &Path) -> bool {
+        self.exclude.as_ref().is_some_and(|set| set.is_match(rel))
+    }
+}
+
+fn build_set(patterns: &[String]) -> Result<Option<GlobSet>, globset::Error> {
+    if patterns.is_empty() {
+        return Ok(None);
+    }
+
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        builder.add(Glob::new(pattern)?);
+    }
+
+    builder.build().map(Some)
+}
+
+/// Walks `root` recursively, yielding every file that passes `filter`.
+///
+/// If `root` is a file it is yielded as-is. Excluded directories are not
+/// descended into. Entries that can't be read are yielded as errors so the
+/// caller can report them.
+pub fn walk<'a>(
+    root: &'a Path,
+    filter: &'a Filter,
+) -> impl Iterator<Item = Result<PathBuf, walkdir::Error>> + 'a {
+    WalkDir::new(root)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_entry(move |entry| {
+            let rel = entry.path().strip_prefix(root).unwrap_or(entry.path());
+            entry.depth() == 0 || !entry.file_type().is_dir() || !filter.is_excluded(rel)
+        })
+        .filter_map(move |entry| match entry {
+            Ok(entry) if entry.file_type().is_file() => {
+                let rel = entry.path().strip_prefix(root).unwrap_or(entry.path());
+                if entry.depth() == 0 || filter.is_match(rel) {
+                    Some(Ok(entry.into_path()))
+                } else {
+                    None
+                }
+            }
+            Ok(_) => None,
+            Err(e) => Some(Err(e)),
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::tempdir;
+
+    fn collect(root: &Path, filter: &Filter) -> Vec<PathBuf> {
+        walk(root, filter)
+            .map(|p| p.unwrap().strip_prefix(root).unwrap().to_path_buf())
+            .collect()
+    }
+
+    #[test]
+    fn test_walk_recursive() {
+        let dir = tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("a/b")).unwrap();
+        fs::write(dir.path().join("top.txt"), "").unwrap();
+        fs::write(dir.path().join("a/mid.txt"), "").unwrap();
+        fs::write(dir.path().join("a/b/deep.md"), "").unwrap();
+
+        let files = collect(dir.path(), &Filter::default());
+
+        assert_eq!(
+            files,
+            vec![
+                PathBuf::from("a/b/deep.md"),
+                PathBuf::from("a/mid.txt"),
+                PathBuf::from("top.txt"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_walk_include_exclude() {
+        let dir = tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("docs")).unwrap();
+        fs::create_dir_all(dir.path().join("target")).unwrap();
+        fs::write(dir.path().join("docs/intro.txt"), "").unwrap();
+        fs::write(dir.path().join("docs/notes.md"), "").unwrap();
+        fs::write(dir.path().join("target/out.txt"), "").unwrap();
+
+        let filter = Filter::new(&["**/*.txt".to_string()], &["target".to_string()]).unwrap();
+        let files = collect(dir.path(), &filter);
+
+        assert_eq!(files, vec![PathBuf::from("docs/intro.txt")]);
+    }
+
+    #[test]
+    fn test_walk_single_file() {
+        let dir = tempdir().unwrap();
+        let file_path = dir.path().join("test.txt");
+        fs::write(&file_path, "").unwrap();
+
+        let files: Vec<_> = walk(&file_path, &Filter::default())
+            .map(Result::unwrap)
+            .collect();
+
+        assert_eq!(files, vec![file_path]);
+    }
+
+    #[test]
+    fn test_invalid_glob() {
+        assert!(Filter::new(&["a[".to_string()], &[]).is_err());
+    }
+}
@@ -0,0 +1,175 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// Where a document was read from: a file, a file inside an archive, which
+/// is shown as `bundle.zip!/docs/intro.md`, or a record in a structured file,
+/// which is shown as `tickets.jsonl#T-42`
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct DocPath {
+    /// The file on disk, which is the archive for archive members
+    path: PathBuf,
+    /// The document's path inside the archive
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    member: Option<String>,
+    /// The id of the record in the file
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    record: Option<String>,
+}
+
+impl DocPath {
+    /// A document that is a whole file
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            member: None,
+            record: None,
+        }
+    }
+
+    /// A document inside the archive at `archive`
+    pub fn in_archive(archive: impl Into<PathBuf>, member: &str) -> Self {
+        Self {
+            path: archive.into(),
+            member: Some(
+                member
+                    .trim_start_matches("./")
+                    .trim_start_matches('/')
+                    .to_string(),
+            ),
+            record: None,
+        }
+    }
+
+    /// A record of the JSON, JSONL or CSV file at `path`
+    pub fn record(path: impl Into<PathBuf>, id: &str) -> Self {
+        Self {
+            path: path.into(),
+            member: None,
+            record: Some(id.to_string()),
+        }
+    }
+
+    /// Reads a path written the way documents are shown, as a file, an
+    /// archive member or a record
+    pub fn parse(path: &str) -> Self {
+        if let Some((archive, member)) = path.split_once("!/") {
+            return Self::in_archive(archive, member);
+        }
+        // Repeated record ids have their position added after another `#`
+        match path.split_once('#') {
+            Some((file, id)) if !file.is_empty() && !id.is_empty() => Self::record(file, id),
+            _ => Self::new(path),
+        }
+    }
+
+    /// The same document, read from the file at `path` instead
+    pub fn with_fs_path(self, path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            ..self
+        }
+    }
+
+    /// The file on disk the document was read from
+    pub fn fs_path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The document's path inside its archive, if it came from one
+    pub fn member(&self) -> Option<&str> {
+        self.member.as_deref()
+    }
+
+    /// The id of the record, if the document is one
+    pub fn record_id(&self) -> Option<&str> {
+        self.record.as_deref()
+    }
+
+    /// The document's extension, from its name inside the archive for
+    /// archive members
+    pub fn extension(&self) -> Option<&str> {
+        let name = self
+            .member
+            .as_deref()
+            .map_or(self.path.as_path(), Path::new);
+        name.extension().and_then(|ext| ext.to_str())
+    }
+}
+
+impl fmt::Display for DocPath {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match (&self.record, &self.member) {
+            (Some(id), _) => write!(f, "{}#{}", self.path.display(), id),
+            (None, Some(member)) => write!(f, "{}!/{}", self.path.display(), member),
+            (None, None) => write!(f, "{}", self.path.display()),
+        }
+    }
+}
+
+impl From<PathBuf> for DocPath {
+    fn from(path: PathBuf) -> Self {
+        Self::new(path)
+    }
+}
+
+impl From<&str> for DocPath {
+    fn from(path: &str) -> Self {
+        Self::new(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn display() {
+        assert_eq!(DocPath::new("docs/intro.md").to_string(), "docs/intro.md");
+        assert_eq!(
+            DocPath::in_archive("bundle.zip", "./docs/intro.md").to_string(),
+            "bundle.zip!/docs/intro.md"
+        );
+        assert_eq!(
+            DocPath::record("tickets.jsonl", "T-42").to_string(),
+            "tickets.jsonl#T-42"
+        );
+    }
+
+    /// Every kind of path should read back from how it is shown
+    #[test]
+    fn parse() {
+        for path in [
+            DocPath::new("docs/intro.md"),
+            DocPath::in_archive("bundle.zip", "docs/intro.md"),
+            DocPath::record("exports/a.csv", "1"),
+            DocPath::record("exports/a.csv", "1#3"),
+            DocPath::in_archive("bundle.zip", "docs/#1.md"),
+        ] {
+            assert_eq!(DocPath::parse(&path.to_string()), path);
+        }
+        assert_eq!(DocPath::parse("#1"), DocPath::new("#1"));
+    }
+
+    /// Members should take their extension from their own name
+    #[test]
+    fn extension() {
+        assert_eq!(DocPath::new("notes.txt").extension(), Some("txt"));
+        assert_eq!(
+            DocPath::in_archive("bundle.tar.gz", "intro.md").extension(),
+            Some("md")
+        );
+        assert_eq!(DocPath::new("README").extension(), None);
+    }
+
+    /// Plain files should serialise as they did before archives existed
+    #[test]
+    fn serialise() {
+        let json = serde_json::to_string(&DocPath::new("a.txt")).unwrap();
+        assert_eq!(json, r#"{"path":"a.txt"}"#);
+
+        let member = DocPath::in_archive("b.zip", "c.md");
+        let json = serde_json::to_string(&member).unwrap();
+        assert_eq!(serde_json::from_str::<DocPath>(&json).unwrap(), member);
+    }
+}
@@ -0,0 +1,204 @@
+use flate2::read::GzDecoder;
+use std::io::{self, Cursor, Read};
+
+use super::Member;
+
+/// Members bigger than this once decompressed are skipped, so a small
+/// archive can't expand to fill memory
+const MAX_MEMBER_LEN: u64 = 64 * 1024 * 1024;
+
+/// Archives whose members add up to more than this once decompressed are
+/// rejected, however small each member is
+const MAX_ARCHIVE_LEN: u64 = 256 * 1024 * 1024;
+
+/// Reads every file in a zip archive or gzipped tarball, in archive order.
+/// Returns `None` for gzipped files that aren't tarballs, and for anything
+/// that isn't an archive at all.
+pub fn members(bytes: &[u8], mime: &str) -> io::Result<Option<Vec<Member>>> {
+    members_within(bytes, mime, MAX_ARCHIVE_LEN)
+}
+
+/// Reads the members of an archive, failing if they add up to more than
+/// `limit` bytes
+fn members_within(bytes: &[u8], mime: &str, limit: u64) -> io::Result<Option<Vec<Member>>> {
+    match mime {
+        "application/zip" => zip_members(bytes, limit).map(Some),
+        "application/gzip" => {
+            // The members of a tarball are never bigger than the tarball itself
+            let mut tar = Vec::new();
+            GzDecoder::new(bytes)
+                .take(limit + 1)
+                .read_to_end(&mut tar)?;
+            if tar.len() as u64 > limit {
+                return Err(too_big(limit));
+            }
+            if !is_tar(&tar) {
+                return Ok(None);
+            }
+            tar_members(&tar).map(Some)
+        }
+        _ => Ok(None),
+    }
+}
+
+fn zip_members(bytes: &[u8], limit: u64) -> io::Result<Vec<Member>> {
+    let mut archive = zip::ZipArchive::new(Cursor::new(bytes)).map_err(invalid_data)?;
+    let mut members = Vec::new();
+    let mut left = limit;
+
+    for i in 0..archive.len() {
+        let file = archive.by_index(i).map_err(invalid_data)?;
+        if !file.is_file() || file.size() > MAX_MEMBER_LEN {
+            continue;
+        }
+
+        let name = file.name().to_string();
+        // Read one byte past what is left, to tell when it runs out
+        let bytes = read_member(file.take(left + 1))?;
+        left = left
+            .checked_sub(bytes.len() as u64)
+            .ok_or_else(|| too_big(limit))?;
+        members.push((name, bytes));
+    }
+
+    Ok(members)
+}
+
+fn tar_members(bytes: &[u8]) -> io::Result<Vec<Member>> {
+    let mut archive = tar::Archive::new(bytes);
+    let mut members = Vec::new();
+
+    for entry in archive.entries()? {
+        let entry = entry?;
+        if !entry.header().entry_type().is_file() || entry.size() > MAX_MEMBER_LEN {
+            continue;
+        }
+
+        let name = entry.path()?.to_string_lossy().into_owned();
+        members.push((name, read_member(entry)?));
+    }
+
+    Ok(members)
+}
+
+/// Reads a member, trusting its declared size no further than the limit
+fn read_member(member: impl Read) -> io::Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    member.take(MAX_MEMBER_LEN).read_to_end(&mut bytes)?;
+    Ok(bytes)
+}
+
+/// Whether decompressed data is a POSIX or GNU tar file
+fn is_tar(bytes: &[u8]) -> bool {
+    bytes.get(257..262) == Some(b"ustar")
+}
+
+fn invalid_data(e: zip::result::ZipError) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e)
+}
+
+fn too_big(limit: u64) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("archive is bigger than {} bytes once decompressed", limit),
+    )
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use std::io::Write;
+    use zip::write::SimpleFileOptions;
+
+    /// A zip archive of `(name, content)` files
+    pub fn zip(files: &[(&str, &str)]) -> Vec<u8> {
+        let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, content) in files {
+            writer
+                .start_file(*name, SimpleFileOptions::default())
+                .unwrap();
+            writer.write_all(content.as_bytes()).unwrap();
+        }
+        writer.finish().unwrap().into_inner()
+    }
+
+    /// A gzipped tarball of `(name, content)` files
+    pub fn tar_gz(files: &[(&str, &str)]) -> Vec<u8> {
+        let mut builder = tar::Builder::new(GzEncoder::new(Vec::new(), Compression::default()));
+        for (name, content) in files {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(content.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder
+                .append_data(&mut header, name, content.as_bytes())
+                .unwrap();
+        }
+        builder.into_inner().unwrap().finish().unwrap()
+    }
+
+    fn names(members: Option<Vec<Member>>) -> Vec<String> {
+        members.unwrap().into_iter().map(|(name, _)| name).collect()
+    }
+
+    #[test]
+    fn zip_archive() {
+        let bytes = zip(&[("docs/intro.md", "# Intro"), ("notes.txt", "notes")]);
+
+        let members = members(&bytes, "application/zip").unwrap();
+
+        assert_eq!(names(members), vec!["docs/intro.md", "notes.txt"]);
+    }
+
+    #[test]
+    fn tar_gz_archive() {
+        let bytes = tar_gz(&[("docs/intro.md", "# Intro")]);
+
+        let members = members(&bytes, "application/gzip").unwrap().unwrap();
+
+        assert_eq!(
+            members,
+            vec![("docs/intro.md".to_string(), b"# Intro".to_vec())]
+        );
+    }
+
+    /// A gzipped file that isn't a tarball is left to the extractors
+    #[test]
+    fn plain_gzip() {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(b"just some text").unwrap();
+        let bytes = encoder.finish().unwrap();
+
+        assert!(members(&bytes, "application/gzip").unwrap().is_none());
+    }
+
+    /// Archives should be rejected once their members add up to too much,
+    /// however small each of them is
+    #[test]
+    fn size_limit() {
+        let files = [("a.txt", "0123456789"), ("b.txt", "0123456789")];
+
+        let zipped = zip(&files);
+        assert_eq!(
+            names(members_within(&zipped, "application/zip", 20).unwrap()).len(),
+            2
+        );
+        let err = members_within(&zipped, "application/zip", 15).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+        // Tarballs are limited by their size once unzipped, headers and all
+        let tarball = tar_gz(&files);
+        assert!(members_within(&tarball, "application/gzip", 10_240).is_ok());
+        let err = members_within(&tarball, "application/gzip", 1024).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn corrupt_zip() {
+        let err = members(b"PK\x03\x04 not really", "application/zip").unwrap_err();
+
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+}
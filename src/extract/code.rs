@@ -0,0 +1,273 @@
+use super::Content;
+use crate::ranker::{COMMENT, IDENTIFIER, STRING};
+
+/// What a programming language's comments, strings and keywords look like
+pub struct Syntax {
+    line_comments: &'static [&'static str],
+    block_comment: Option<(&'static str, &'static str)>,
+    /// Whether block comments can contain other block comments
+    nested_comments: bool,
+    /// Quotes that open a string, longest first so `"""` wins over `"`
+    quotes: &'static [&'static str],
+    /// Letters that may come straight before a string's opening quote
+    string_prefixes: &'static str,
+    /// Rust's `r#"..."#` strings and `'c'` character literals
+    rust_literals: bool,
+    keywords: &'static [&'static str],
+}
+
+pub const RUST: Syntax = Syntax {
+    line_comments: &["//"],
+    block_comment: Some(("/*", "*/")),
+    nested_comments: true,
+    quotes: &["\""],
+    string_prefixes: "br",
+    rust_literals: true,
+    keywords: &[
+        "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum",
+        "extern", "false", "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move",
+        "mut", "pub", "ref", "return", "self", "Self", "static", "struct", "super", "trait",
+        "true", "type", "unsafe", "use", "where", "while",
+    ],
+};
+
+pub const PYTHON: Syntax = Syntax {
+    line_comments: &["#"],
+    block_comment: None,
+    nested_comments: false,
+    quotes: &["\"\"\"", "'''", "\"", "'"],
+    string_prefixes: "bfruBFRU",
+    rust_literals: false,
+    keywords: &[
+        "False", "None", "True", "and", "as", "assert", "async", "await", "break", "class",
+        "continue", "def", "del", "elif", "else", "except", "finally", "for", "from", "global",
+        "if", "import", "in", "is", "lambda", "nonlocal", "not", "or", "pass", "raise", "return",
+        "try", "while", "with", "yield",
+    ],
+};
+
+/// Splits source code into its comments, string literals and identifiers,
+/// each in their own field. Keywords, numbers and punctuation are dropped.
+pub fn extract(source: &str, syntax: &Syntax) -> Content {
+    let mut content = Content::default();
+    let mut identifiers = String::new();
+    let mut rest = source;
+
+    while let Some(c) = rest.chars().next() {
+        if c == '\n' && !identifiers.is_empty() && !identifiers.ends_with('\n') {
+            identifiers.push('\n');
+        }
+
+        if let Some(marker) = syntax.line_comments.iter().find(|m| rest.starts_with(*m)) {
+            let end = rest.find('\n').unwrap_or(rest.len());
+            // Drop the marker along with doc comment markers like `///` and `//!`
+            let text = rest[..end].trim_start_matches(*marker);
+            content.push(COMMENT, text.trim_start_matches(['/', '!']).trim());
+            rest = &rest[end..];
+            continue;
+        }
+
+        if let Some((open, close)) = syntax
+            .block_comment
+            .filter(|(open, _)| rest.starts_with(open))
+        {
+            let (text, after) =
+                block_comment(&rest[open.len()..], open, close, syntax.nested_comments);
+            for line in text.lines() {
+                content.push(COMMENT, line.trim().trim_start_matches(['*', '!']).trim());
+            }
+            rest = after;
+            continue;
+        }
+
+        if c == '\'' && syntax.rust_literals {
+            rest = skip_char_or_lifetime(rest);
+            continue;
+        }
+
+        if let Some(quote) = syntax.quotes.iter().find(|q| rest.starts_with(*q)) {
+            let (text, after) = string(&rest[quote.len()..], quote, true);
+            content.push(STRING, text);
+            rest = after;
+            continue;
+        }
+
+        if c.is_numeric() {
+            let end = rest
+                .find(|c: char| !(c.is_alphanumeric() || c == '_' || c == '.'))
+                .unwrap_or(rest.len());
+            rest = &rest[end..];
+            continue;
+        }
+
+        if c.is_alphabetic() || c == '_' {
+            let end = rest
+                .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+                .unwrap_or(rest.len());
+            let (word, after) = rest.split_at(end);
+
+            if let Some((text, after)) = prefixed_literal(word, after, syntax) {
+                if let Some(text) = text {
+                    content.push(STRING, text);
+                }
+                rest = after;
+                continue;
+            }
+
+            if !syntax.keywords.contains(&word) {
+                if !identifiers.is_empty() && !identifiers.ends_with('\n') {
+                    identifiers.push(' ');
+                }
+                identifiers.push_str(word);
+            }
+            rest = after;
+            continue;
+        }
+
+        rest = &rest[c.len_utf8()..];
+    }
+    content.push(IDENTIFIER, identifiers.trim_end());
+
+    content
+}
+
+/// A literal that starts with a prefix like `r` or `b`, if `word` is one.
+/// Returns the string's text, or `None` for a byte character like `b'a'`,
+/// and what follows the literal.
+fn prefixed_literal<'a>(
+    word: &str,
+    after: &'a str,
+    syntax: &Syntax,
+) -> Option<(Option<&'a str>, &'a str)> {
+    if word.len() > 2 || !word.chars().all(|c| syntax.string_prefixes.contains(c)) {
+        return None;
+    }
+
+    if syntax.rust_literals {
+        if word == "b" && after.starts_with('\'') {
+            return Some((None, skip_char_or_lifetime(after)));
+        }
+        if word.contains('r') {
+            let hashes = after.len() - after.trim_start_matches('#').len();
+            let after = after[hashes..].strip_prefix('"')?;
+            let close = format!("\"{}", "#".repeat(hashes));
+            let (text, after) = string(after, &close, false);
+            return Some((Some(text), after));
+        }
+    }
+
+    let quote = syntax.quotes.iter().find(|q| after.starts_with(*q))?;
+    let (text, after) = string(&after[quote.len()..], quote, true);
+    Some((Some(text), after))
+}
+
+/// Reads a string up to its closing quote, returning its text and what
+/// follows. Unterminated strings run to the end of the source.
+fn string<'a>(rest: &'a str, close: &str, escapes: bool) -> (&'a str, &'a str) {
+    let mut chars = rest.char_indices();
+
+    while let Some((i, c)) = chars.next() {
+        if c == '\\' && escapes {
+            chars.next();
+        } else if rest[i..].starts_with(close) {
+            return (&rest[..i], &rest[i + close.len()..]);
+        }
+    }
+
+    (rest, "")
+}
+
+/// Reads a block comment whose opening marker has been consumed, returning
+/// its text and what follows
+fn block_comment<'a>(rest: &'a str, open: &str, close: &str, nested: bool) -> (&'a str, &'a str) {
+    let mut depth = 1;
+    let mut i = 0;
+
+    while i < rest.len() {
+        if rest[i..].starts_with(close) {
+            depth -= 1;
+            if depth == 0 {
+                return (&rest[..i], &rest[i + close.len()..]);
+            }
+            i += close.len();
+        } else if nested && rest[i..].starts_with(open) {
+            depth += 1;
+            i += open.len();
+        } else {
+            i += rest[i..].chars().next().map_or(1, char::len_utf8);
+        }
+    }
+
+    (rest, "")
+}
+
+/// Skips a Rust character literal like `'a'` or `'\n'`, or a lifetime like
+/// `'a`, neither of which is worth indexing
+fn skip_char_or_lifetime(rest: &str) -> &str {
+    let after = &rest[1..];
+    let mut chars = after.chars();
+
+    match (chars.next(), chars.next()) {
+        (Some('\\'), _) => string(after, "'", true).1,
+        (Some(c), Some('\'')) => &after[c.len_utf8() + 1..],
+        _ => after.trim_start_matches(|c: char| c.is_alphanumeric() || c == '_'),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Should separate comments, strings and identifiers, dropping keywords
+    #[test]
+    fn rust() {
+        let content = extract(
+            "//! Query parsing\n\
+             /* Outer /* nested */ still comment */\n\
+             /// Parses a `query`\n\
+             pub fn parse_query<'a>(text: &'a str) -> Query {\n    \
+                 let quote = '\"';\n    \
+                 let newline = b'\\n';\n    \
+                 let raw = r#\"raw \"quoted\" text\"#;\n    \
+                 println!(\"parsing {} \\\"now\\\"\", 42u32);\n\
+             }\n",
+            &RUST,
+        );
+
+        assert_eq!(
+            content.field(COMMENT),
+            Some("Query parsing\nOuter /* nested */ still comment\nParses a `query`")
+        );
+        assert_eq!(
+            content.field(STRING),
+            Some("raw \"quoted\" text\nparsing {} \\\"now\\\"")
+        );
+        assert_eq!(
+            content.field(IDENTIFIER),
+            Some("parse_query text str Query\nquote\nnewline\nraw\nprintln")
+        );
+    }
+
+    /// Should handle Python's comments, docstrings and string prefixes
+    #[test]
+    fn python() {
+        let content = extract(
+            "# Helpers\n\
+             class HTTPClient:\n    \
+                 \"\"\"Talks to the server.\n\n    Retries on failure.\"\"\"\n    \
+                 def get(self, url=f'{base}/path', raw=rb\"\\d+\"):\n        \
+                 return None  # unreachable\n",
+            &PYTHON,
+        );
+
+        assert_eq!(content.field(COMMENT), Some("Helpers\nunreachable"));
+        assert_eq!(
+            content.field(STRING),
+            Some("Talks to the server.\n\n    Retries on failure.\n{base}/path\n\\d+")
+        );
+        assert_eq!(
+            content.field(IDENTIFIER),
+            Some("HTTPClient\nget self url raw")
+        );
+    }
+}
@@ -0,0 +1,301 @@
+use std::collections::BTreeMap;
+
+use super::Content;
+use crate::ranker::{BODY, HEADING, TITLE};
+
+/// Elements whose content is never text
+const SKIPPED: &[&str] = &["script", "style", "template", "noscript"];
+
+/// Elements that separate their content from the surrounding text
+const BLOCKS: &[&str] = &[
+    "address",
+    "article",
+    "aside",
+    "blockquote",
+    "br",
+    "dd",
+    "div",
+    "dl",
+    "dt",
+    "figcaption",
+    "figure",
+    "footer",
+    "form",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "header",
+    "hr",
+    "li",
+    "main",
+    "nav",
+    "ol",
+    "p",
+    "pre",
+    "section",
+    "table",
+    "td",
+    "th",
+    "title",
+    "tr",
+    "ul",
+];
+
+/// Pulls the text out of an HTML document. The `<title>` and `<h1>`-`<h6>`
+/// elements go to their own fields, and everything else visible to the body.
+pub fn extract(html: &str) -> Content {
+    let mut fields: BTreeMap<&str, String> = BTreeMap::new();
+    let mut field = BODY;
+    let mut rest = html;
+
+    while let Some(lt) = rest.find('<') {
+        fields.entry(field).or_default().push_str(&rest[..lt]);
+        rest = &rest[lt..];
+
+        if let Some(after) = rest.strip_prefix("<!--") {
+            rest = after.find("-->").map_or("", |end| &after[end + 3..]);
+            continue;
+        }
+
+        // Doctypes, CDATA and processing instructions
+        if rest.starts_with("<!") || rest.starts_with("<?") {
+            rest = rest.find('>').map_or("", |end| &rest[end + 1..]);
+            continue;
+        }
+
+        let Some(tag) = Tag::parse(rest) else {
+            // A lone `<`, as in `a < b`
+            fields.entry(field).or_default().push('<');
+            rest = &rest[1..];
+            continue;
+        };
+        rest = &rest[tag.len..];
+
+        if BLOCKS.contains(&tag.name.as_str()) {
+            fields.entry(field).or_default().push('\n');
+        }
+
+        match tag.name.as_str() {
+            name if SKIPPED.contains(&name) && !tag.closing => {
+                rest = skip_element(rest, name);
+            }
+            "title" => field = if tag.closing { BODY } else { TITLE },
+            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+                field = if tag.closing { BODY } else { HEADING };
+            }
+            _ => {}
+        }
+    }
+    fields.entry(field).or_default().push_str(rest);
+
+    let mut content = Content::default();
+    for (field, text) in fields {
+        content.push(field, &clean(&decode_entities(&text)));
+    }
+
+    content
+}
+
+/// The text of an HTML fragment, with tags removed and entities decoded
+pub fn text(html: &str) -> String {
+    extract(html)
+        .fields()
+        .map(|(_, text)| text)
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+struct Tag {
+    /// Lowercased element name
+    name: String,
+    closing: bool,
+    /// Length of the tag in bytes, including the angle brackets
+    len: usize,
+}
+
+impl Tag {
+    /// Parses the tag at the start of `input`, which begins with `<`
+    fn parse(input: &str) -> Option<Tag> {
+        let after = &input[1..];
+        let (closing, after) = match after.strip_prefix('/') {
+            Some(after) => (true, after),
+            None => (false, after),
+        };
+
+        let name_len = after
+            .find(|c: char| !c.is_ascii_alphanumeric())
+            .unwrap_or(after.len());
+        let name = &after[..name_len];
+        if !name.starts_with(|c: char| c.is_ascii_alphabetic()) {
+            return None;
+        }
+        if !after[name_len..].starts_with(|c: char| c.is_whitespace() || c == '/' || c == '>') {
+            return None;
+        }
+
+        // Find the closing `>`, ignoring any inside quoted attribute values
+        let mut quote = None;
+        let end = after[name_len..].char_indices().find_map(|(i, c)| {
+            match (quote, c) {
+                (None, '"' | '\'') => quote = Some(c),
+                (Some(q), c) if c == q => quote = None,
+                (None, '>') => return Some(i),
+                _ => {}
+            }
+            None
+        })?;
+
+        Some(Tag {
+            name: name.to_ascii_lowercase(),
+            closing,
+            len: input.len() - after.len() + name_len + end + 1,
+        })
+    }
+}
+
+/// Skips past the closing tag of a `name` element whose content isn't text
+fn skip_element<'a>(rest: &'a str, name: &str) -> &'a str {
+    let needle = format!("</{}", name);
+    let found = rest
+        .as_bytes()
+        .windows(needle.len())
+        .position(|w| w.eq_ignore_ascii_case(needle.as_bytes()));
+
+    match found {
+        Some(start) => {
+            let after = &rest[start..];
+            after.find('>').map_or("", |end| &after[end + 1..])
+        }
+        None => "",
+    }
+}
+
+/// Collapses runs of whitespace and drops empty lines
+fn clean(text: &str) -> String {
+    text.lines()
+        .map(|line| line.split_whitespace().collect::<Vec<_>>().join(" "))
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Named entities for U+00A0 to U+00FF, in code point order
+const LATIN1: [&str; 96] = [
+    "nbsp", "iexcl", "cent", "pound", "curren", "yen", "brvbar", "sect", "uml", "copy", "ordf",
+    "laquo", "not", "shy", "reg", "macr", "deg", "plusmn", "sup2", "sup3", "acute", "micro",
+    "para", "middot", "cedil", "sup1", "ordm", "raquo", "frac14", "frac12", "frac34", "iquest",
+    "Agrave", "Aacute", "Acirc", "Atilde", "Auml", "Aring", "AElig", "Ccedil", "Egrave", "Eacute",
+    "Ecirc", "Euml", "Igrave", "Iacute", "Icirc", "Iuml", "ETH", "Ntilde", "Ograve", "Oacute",
+    "Ocirc", "Otilde", "Ouml", "times", "Oslash", "Ugrave", "Uacute", "Ucirc", "Uuml", "Yacute",
+    "THORN", "szlig", "agrave", "aacute", "acirc", "atilde", "auml", "aring", "aelig", "ccedil",
+    "egrave", "eacute", "ecirc", "euml", "igrave", "iacute", "icirc", "iuml", "eth", "ntilde",
+    "ograve", "oacute", "ocirc", "otilde", "ouml", "divide", "oslash", "ugrave", "uacute", "ucirc",
+    "uuml", "yacute", "thorn", "yuml",
+];
+
+fn named_entity(name: &str) -> Option<char> {
+    let c = match name {
+        "amp" => '&',
+        "lt" => '<',
+        "gt" => '>',
+        "quot" => '"',
+        "apos" => '\'',
+        "ndash" => '–',
+        "mdash" => '—',
+        "lsquo" => '‘',
+        "rsquo" => '’',
+        "ldquo" => '“',
+        "rdquo" => '”',
+        "bull" => '•',
+        "hellip" => '…',
+        "euro" => '€',
+        "trade" => '™',
+        // Non-breaking spaces separate words like any other space
+        "nbsp" => ' ',
+        _ => {
+            let i = LATIN1.iter().position(|n| *n == name)?;
+            return char::from_u32(0xA0 + i as u32);
+        }
+    };
+
+    Some(c)
+}
+
+/// Decodes named, decimal and hexadecimal character references.
+/// Anything that isn't a known reference is left as it is.
+pub fn decode_entities(text: &str) -> String {
+    let mut decoded = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(amp) = rest.find('&') {
+        decoded.push_str(&rest[..amp]);
+        rest = &rest[amp..];
+
+        let reference = rest[1..]
+            .find(';')
+            .filter(|&end| end <= 32)
+            .map(|end| &rest[1..end + 1]);
+
+        let c = reference.and_then(|reference| match reference.strip_prefix('#') {
+            Some(number) => match number.strip_prefix(['x', 'X']) {
+                Some(hex) => u32::from_str_radix(hex, 16).ok(),
+                None => number.parse().ok(),
+            }
+            .and_then(char::from_u32),
+            None => named_entity(reference),
+        });
+
+        match (c, reference) {
+            (Some(c), Some(reference)) => {
+                decoded.push(c);
+                rest = &rest[reference.len() + 2..];
+            }
+            _ => {
+                decoded.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    decoded.push_str(rest);
+
+    decoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Should split out the title and headings, and drop scripts and styles
+    #[test]
+    fn fields() {
+        let content = extract(
+            "<!DOCTYPE html><html><head><title>The Guide</title>\
+             <style>body { color: red }</style></head>\
+             <body><h1 class=\"top\">Intro</h1><p>Hello <b>there</b></p>\
+             <script type=\"text/javascript\">if (a < b) { alert('</p>') }</script>\
+             <!-- hidden --><h2>Usage</h2><div>Run it</div></body></html>",
+        );
+
+        assert_eq!(content.field(TITLE), Some("The Guide"));
+        assert_eq!(content.field(HEADING), Some("Intro\nUsage"));
+        assert_eq!(content.body(), "Hello there\nRun it");
+    }
+
+    /// Should decode named and numeric references, leaving unknown ones alone
+    #[test]
+    fn entities() {
+        assert_eq!(
+            decode_entities("Caf&eacute; &amp; cr&#232;me &#x2014; &bogus; AT&T"),
+            "Café & crème — &bogus; AT&T"
+        );
+    }
+
+    /// Should treat a `<` that doesn't start a tag as text
+    #[test]
+    fn stray_angle_bracket() {
+        assert_eq!(text("<p>a < b and c<d</p>"), "a < b and c<d");
+    }
+}
@@ -0,0 +1,289 @@
+use super::html;
+use super::Content;
+use crate::ranker::{BODY, HEADING, TITLE};
+
+/// Pulls the text out of a Markdown document.
+///
+/// The title comes from a `title:` in the front matter, or else the first
+/// level 1 heading. Other headings go to their own field, and the rest is
+/// body text with links reduced to their text, emphasis and code markers
+/// dropped and any HTML stripped.
+/// Code blocks are kept as they are.
+pub fn extract(markdown: &str) -> Content {
+    let mut content = Content::default();
+    let mut lines = markdown.lines().peekable();
+
+    if lines.next_if(|line| line.trim_end() == "---").is_some() {
+        for line in lines.by_ref() {
+            if matches!(line.trim_end(), "---" | "...") {
+                break;
+            }
+            if let Some(title) = line.strip_prefix("title:") {
+                content.push(TITLE, title.trim().trim_matches(['"', '\'']));
+            }
+        }
+    }
+
+    let mut fence: Option<&str> = None;
+    let mut raw_html: Option<&str> = None;
+
+    while let Some(line) = lines.next() {
+        let trimmed = line.trim();
+
+        if let Some(marker) = fence {
+            if trimmed.starts_with(marker) {
+                fence = None;
+            } else {
+                content.push(BODY, line);
+            }
+            continue;
+        }
+        if let Some(marker) = ["```", "~~~"].into_iter().find(|m| trimmed.starts_with(m)) {
+            fence = Some(marker);
+            continue;
+        }
+
+        // Raw script and style blocks, which may span several lines
+        if let Some(end) = raw_html {
+            if trimmed.to_ascii_lowercase().contains(end) {
+                raw_html = None;
+            }
+            continue;
+        }
+        let lower = trimmed.to_ascii_lowercase();
+        if let Some((_, end)) = [("<script", "</script"), ("<style", "</style")]
+            .into_iter()
+            .find(|(start, _)| lower.starts_with(start))
+        {
+            if !lower.contains(end) {
+                raw_html = Some(end);
+            }
+            continue;
+        }
+
+        if let Some((level, text)) = atx_heading(trimmed) {
+            push_heading(&mut content, level, text);
+            continue;
+        }
+
+        // A line of text underlined with `=` or `-` is a heading too
+        if !trimmed.is_empty() {
+            let underline = lines.peek().map(|next| next.trim()).and_then(|next| {
+                match next.chars().next()? {
+                    c @ ('=' | '-') if next.chars().all(|n| n == c) => Some(c),
+                    _ => None,
+                }
+            });
+            if let Some(c) = underline {
+                lines.next();
+                push_heading(&mut content, if c == '=' { 1 } else { 2 }, trimmed);
+                continue;
+            }
+        }
+
+        if is_link_definition(trimmed) {
+            continue;
+        }
+
+        content.push(BODY, &inline(line));
+    }
+
+    content
+}
+
+/// Parses a `## Heading ##` line into its level and text
+fn atx_heading(line: &str) -> Option<(usize, &str)> {
+    let level = line.chars().take_while(|&c| c == '#').count();
+    let text = &line[level..];
+    if !(1..=6).contains(&level) || !(text.is_empty() || text.starts_with([' ', '\t'])) {
+        return None;
+    }
+
+    Some((level, text.trim().trim_end_matches('#').trim_end()))
+}
+
+fn push_heading(content: &mut Content, level: usize, text: &str) {
+    let field = if level == 1 && content.field(TITLE).is_none() {
+        TITLE
+    } else {
+        HEADING
+    };
+
+    content.push(field, &inline(text));
+}
+
+/// A `[label]: url` reference, which has no text of its own
+fn is_link_definition(line: &str) -> bool {
+    line.strip_prefix('[')
+        .and_then(|rest| rest.split_once("]:"))
+        .is_some_and(|(label, _)| !label.is_empty())
+}
+
+/// Reduces `[text](url)`, `![alt](url)` and `[text][ref]` to their text,
+/// drops emphasis and code markers, then strips inline HTML
+fn inline(text: &str) -> String {
+    let text = emphasis(text);
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text.as_str();
+
+    while let Some(open) = rest.find('[') {
+        let (before, after) = rest.split_at(open);
+        out.push_str(before.strip_suffix('!').unwrap_or(before));
+
+        let Some(close) = after.find(']') else {
+            out.push_str(after);
+            rest = "";
+            break;
+        };
+        let label = &after[1..close];
+        let target = &after[close + 1..];
+
+        let skipped = match target.chars().next() {
+            Some('(') => target.find(')').map(|end| end + 1),
+            Some('[') => target.find(']').map(|end| end + 1),
+            _ => None,
+        };
+
+        match skipped {
+            Some(len) => {
+                out.push_str(label);
+                rest = &target[len..];
+            }
+            // Just brackets, keep them as they are
+            None => {
+                if before.ends_with('!') {
+                    out.push('!');
+                }
+                out.push('[');
+                rest = &after[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+
+    html::text(&out)
+}
+
+/// Drops the backticks around inline code, keeping the code as it is, and
+/// the `*` and `_` runs around emphasised text. A run that stands alone
+/// between spaces, like `2 * 3`, or is inside a word, like `snake_case`,
+/// isn't emphasis and is kept.
+fn emphasis(text: &str) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    let run = |from: usize| {
+        let c = chars[from];
+        chars[from..].iter().take_while(|&&n| n == c).count()
+    };
+    let spaced = |c: Option<&char>| c.is_none_or(|c| c.is_whitespace());
+    let wordy = |c: Option<&char>| c.is_some_and(|c| c.is_alphanumeric());
+
+    let mut out = String::with_capacity(text.len());
+    let mut i = 0;
+    while i < chars.len() {
+        match chars[i] {
+            '`' => {
+                // The code runs to the next run of exactly as many backticks
+                let len = run(i);
+                let mut close = i + len;
+                while close < chars.len() && (chars[close] != '`' || run(close) != len) {
+                    close += if chars[close] == '`' { run(close) } else { 1 };
+                }
+                if close < chars.len() {
+                    out.extend(&chars[i + len..close]);
+                    i = close + len;
+                } else {
+                    out.extend(&chars[i..i + len]);
+                    i += len;
+                }
+            }
+            '*' | '_' => {
+                let len = run(i);
+                let before = i.checked_sub(1).and_then(|j| chars.get(j));
+                let after = chars.get(i + len);
+                let literal = (spaced(before) && spaced(after)) || (wordy(before) && wordy(after));
+                if literal {
+                    out.extend(&chars[i..i + len]);
+                }
+                i += len;
+            }
+            c => {
+                out.push(c);
+                i += 1;
+            }
+        }
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Should split out the title and headings in either style
+    #[test]
+    fn headings() {
+        let content = extract(
+            "# Guide\n\nIntro text\n\n## Install ##\nRun it\n\nUsage\n-----\nSearch\n\n# Appendix\n",
+        );
+
+        assert_eq!(content.field(TITLE), Some("Guide"));
+        assert_eq!(content.field(HEADING), Some("Install\nUsage\nAppendix"));
+        assert_eq!(content.body(), "Intro text\nRun it\nSearch");
+    }
+
+    /// The front matter title should win over the first heading
+    #[test]
+    fn front_matter() {
+        let content = extract("---\ntitle: \"Real title\"\ntags: [a]\n---\n# Heading\nText\n");
+
+        assert_eq!(content.field(TITLE), Some("Real title"));
+        assert_eq!(content.field(HEADING), Some("Heading"));
+        assert_eq!(content.body(), "Text");
+    }
+
+    /// Links and images should keep their text but not their targets
+    #[test]
+    fn links() {
+        let content = extract(
+            "See [the docs](https://example.com/docs) and ![a diagram](d.png), \
+             or [this][ref] [x]\n\n[ref]: https://example.com\n",
+        );
+
+        assert_eq!(content.body(), "See the docs and a diagram, or this [x]");
+    }
+
+    /// Emphasis and inline code markers should go, but not the `*` and `_`
+    /// that aren't markers
+    #[test]
+    fn emphasis_and_code() {
+        let content = extract(
+            "Some **bold**, *italic* and __strong__ text\n\
+             Call `parse_query(a*b)` or ``a ` b``, not 2 * 3\n\
+             Keep snake_case and 2*3, ***all*** _of_ it\n",
+        );
+
+        assert_eq!(
+            content.body(),
+            "Some bold, italic and strong text\n\
+             Call parse_query(a*b) or a ` b, not 2 * 3\n\
+             Keep snake_case and 2*3, all of it"
+        );
+    }
+
+    /// Code blocks should be kept, and raw HTML stripped
+    #[test]
+    fn code_and_html() {
+        let content = extract(
+            "```rust\nfn main() {}\n# not a heading\n```\n\
+             <script>\nlet hidden = 1;\n</script>\n\
+             Some <em>emphasised</em> text &amp; more\n",
+        );
+
+        assert_eq!(content.field(TITLE), None);
+        assert_eq!(
+            content.body(),
+            "fn main() {}\n# not a heading\nSome emphasised text & more"
+        );
+    }
+}
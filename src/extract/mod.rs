@@ -0,0 +1,341 @@
+use std::collections::BTreeMap;
+use std::io::{self};
+use std::ops::Range;
+use std::path::Path;
+
+use crate::ranker::BODY;
+
+#[cfg(feature = "archive")]
+pub(crate) mod archive;
+mod code;
+mod html;
+mod markdown;
+#[cfg(feature = "pdf")]
+mod pdf;
+mod records;
+mod registry;
+mod sniff;
+
+#[cfg(feature = "pdf")]
+pub use pdf::PdfError;
+pub use records::{Record, RecordFields};
+pub use registry::Registry;
+pub use sniff::mime_type;
+
+/// Text pulled out of a document, split into named fields such as
+/// `BODY`, `TITLE` and `HEADING`
+#[derive(Debug, Default, PartialEq)]
+pub struct Content {
+    fields: BTreeMap<String, String>,
+    /// Where each page is in the body, for paged formats
+    pages: Vec<Range<usize>>,
+}
+
+impl Content {
+    /// Content that is all body text
+    pub fn text(body: impl Into<String>) -> Self {
+        Self {
+            fields: BTreeMap::from([(BODY.to_string(), body.into())]),
+            pages: Vec::new(),
+        }
+    }
+
+    /// Appends text to a field, on a new line if the field already has some.
+    /// Blank text is ignored.
+    pub fn push(&mut self, field: &str, text: &str) {
+        if text.trim().is_empty() {
+            return;
+        }
+
+        let existing = self.fields.entry(field.to_string()).or_default();
+        if !existing.is_empty() {
+            existing.push('\n');
+        }
+        existing.push_str(text);
+    }
+
+    /// Appends a page to the body, on a new line, and records where it is
+    pub fn push_page(&mut self, text: &str) {
+        let body = self.fields.entry(BODY.to_string()).or_default();
+        if !body.is_empty() {
+            body.push('\n');
+        }
+
+        let start = body.len();
+        body.push_str(text);
+        self.pages.push(start..body.len());
+    }
+
+    /// The body text of each page, empty for formats without pages
+    pub fn pages(&self) -> impl Iterator<Item = &str> {
+        self.pages.iter().map(|page| &self.body()[page.clone()])
+    }
+
+    pub fn field(&self, name: &str) -> Option<&str> {
+        self.fields.get(name).map(String::as_str)
+    }
+
+    /// The main text, empty if there is none
+    pub fn body(&self) -> &str {
+        self.field(BODY).unwrap_or_default()
+    }
+
+    /// Every field with its text, ordered by name
+    pub fn fields(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.fields
+            .iter()
+            .map(|(name, text)| (name.as_str(), text.as_str()))
+    }
+}
+
+/// A document format that text can be pulled out of.
+///
+/// Implement this to teach a [`Registry`] a new format. Closures with the
+/// same signature as [`Extract::extract`] implement it too.
+pub trait Extract: Send + Sync {
+    /// Extracts the content of the file at `path`, whose bytes have already
+    /// been read. Content the extractor can't handle should be rejected
+    /// with `InvalidInput`, so it is skipped rather than reported as a failure.
+    fn extract(&self, path: &Path, bytes: &[u8]) -> io::Result<Content>;
+}
+
+impl<F> Extract for F
+where
+    F: Fn(&Path, &[u8]) -> io::Result<Content> + Send + Sync,
+{
+    fn extract(&self, path: &Path, bytes: &[u8]) -> io::Result<Content> {
+        self(path, bytes)
+    }
+}
+
+/// Decodes the bytes of a text file from UTF-8, UTF-16 with a byte order
+/// mark or Latin-1, and rejects binary data with `InvalidInput`
+pub fn decode_text(bytes: &[u8]) -> io::Result<String> {
+    sniff::decode(bytes).ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "Binary file"))
+}
+
+/// A file inside an archive: its path in the archive and its content
+pub type Member = (String, Vec<u8>);
+
+/// Reads every file inside a zip archive or gzipped tarball, or returns
+/// `None` if `bytes` aren't one. Without the `archive` feature, archives
+/// are rejected with `InvalidInput`.
+pub fn archive_members(bytes: &[u8]) -> io::Result<Option<Vec<Member>>> {
+    archive_members_as(bytes, sniff::mime_type(bytes))
+}
+
+/// Like [`archive_members`], for bytes already sniffed as `mime`
+#[cfg_attr(not(feature = "archive"), allow(unused_variables))]
+pub fn archive_members_as(bytes: &[u8], mime: &str) -> io::Result<Option<Vec<Member>>> {
+    #[cfg(feature = "archive")]
+    return archive::members(bytes, mime);
+
+    #[cfg(not(feature = "archive"))]
+    match mime {
+        "application/zip" | "application/gzip" => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "Archive support needs the `archive` feature",
+        )),
+        _ => Ok(None),
+    }
+}
+
+/// Splits a JSON, JSONL or CSV file into records, each to be indexed as a
+/// document of its own
+pub fn records(path: &Path, bytes: &[u8], fields: &RecordFields) -> io::Result<Vec<Record>> {
+    records::extract(path, &decode_text(bytes)?, fields)
+}
+
+/// Plain text, which is all body
+pub struct PlainText;
+
+impl Extract for PlainText {
+    fn extract(&self, _path: &Path, bytes: &[u8]) -> io::Result<Content> {
+        Ok(Content::text(decode_text(bytes)?))
+    }
+}
+
+/// Markdown, with the title and headings in their own fields
+pub struct Markdown;
+
+impl Extract for Markdown {
+    fn extract(&self, _path: &Path, bytes: &[u8]) -> io::Result<Content> {
+        Ok(markdown::extract(&decode_text(bytes)?))
+    }
+}
+
+/// HTML, with the title and headings in their own fields
+pub struct Html;
+
+impl Extract for Html {
+    fn extract(&self, _path: &Path, bytes: &[u8]) -> io::Result<Content> {
+        Ok(html::extract(&decode_text(bytes)?))
+    }
+}
+
+/// Rust source, split into comments, strings and identifiers
+pub struct Rust;
+
+impl Extract for Rust {
+    fn extract(&self, _path: &Path, bytes: &[u8]) -> io::Result<Content> {
+        Ok(code::extract(&decode_text(bytes)?, &code::RUST))
+    }
+}
+
+/// Python source, split into comments, strings and identifiers
+pub struct Python;
+
+impl Extract for Python {
+    fn extract(&self, _path: &Path, bytes: &[u8]) -> io::Result<Content> {
+        Ok(code::extract(&decode_text(bytes)?, &code::PYTHON))
+    }
+}
+
+/// PDFs, one page at a time. Without the `pdf` feature every PDF is rejected.
+pub struct Pdf;
+
+impl Extract for Pdf {
+    #[cfg(feature = "pdf")]
+    fn extract(&self, _path: &Path, bytes: &[u8]) -> io::Result<Content> {
+        Ok(pdf::extract(bytes)?)
+    }
+
+    #[cfg(not(feature = "pdf"))]
+    fn extract(&self, _path: &Path, _bytes: &[u8]) -> io::Result<Content> {
+        Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "PDF support needs the `pdf` feature",
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ranker::{COMMENT, HEADING, IDENTIFIER, STRING, TITLE};
+    use std::fs;
+    use std::path::PathBuf;
+    use tempfile::tempdir;
+
+    fn extract(path: &Path) -> io::Result<Content> {
+        Registry::default().extract_file(path)
+    }
+
+    #[test]
+    fn test_extract_txt_file() {
+        let dir = tempdir().unwrap();
+        let file_path = dir.path().join("test.txt");
+        fs::write(&file_path, "Hello, world!").unwrap();
+
+        let result = extract(&file_path);
+        assert!(result.is_ok());
+        assert_eq!(result.unwrap().body(), "Hello, world!");
+    }
+
+    #[test]
+    fn test_extract_md_file() {
+        let dir = tempdir().unwrap();
+        let file_path = dir.path().join("test.md");
+        fs::write(
+            &file_path,
+            "# Hello\n\n## World\n\nSome **bold** [text](url)",
+        )
+        .unwrap();
+
+        let content = extract(&file_path).unwrap();
+        assert_eq!(content.field(TITLE), Some("Hello"));
+        assert_eq!(content.field(HEADING), Some("World"));
+        assert_eq!(content.body(), "Some bold text");
+    }
+
+    #[test]
+    fn test_extract_html_file() {
+        let dir = tempdir().unwrap();
+        let file_path = dir.path().join("test.html");
+        fs::write(
+            &file_path,
+            "<html><head><title>Hello</title><script>var x;</script></head>\
+             <body><h2>World</h2><p>Fish &amp; chips</p></body></html>",
+        )
+        .unwrap();
+
+        let content = extract(&file_path).unwrap();
+        assert_eq!(content.field(TITLE), Some("Hello"));
+        assert_eq!(content.field(HEADING), Some("World"));
+        assert_eq!(content.body(), "Fish & chips");
+    }
+
+    #[test]
+    fn test_extract_rs_file() {
+        let dir = tempdir().unwrap();
+        let file_path = dir.path().join("test.rs");
+        fs::write(
+            &file_path,
+            "// Entry point\nfn main() {\n    let greetingText = \"hello\";\n}\n",
+        )
+        .unwrap();
+
+        let content = extract(&file_path).unwrap();
+        assert_eq!(content.field(COMMENT), Some("Entry point"));
+        assert_eq!(content.field(STRING), Some("hello"));
+        assert_eq!(content.field(IDENTIFIER), Some("main\ngreetingText"));
+    }
+
+    #[test]
+    fn test_pages() {
+        let mut content = Content::default();
+        content.push_page("first");
+        content.push_page("");
+        content.push_page("third");
+
+        assert_eq!(content.body(), "first\n\nthird");
+        assert_eq!(
+            content.pages().collect::<Vec<_>>(),
+            vec!["first", "", "third"]
+        );
+    }
+
+    #[test]
+    fn test_extract_without_extension() {
+        let dir = tempdir().unwrap();
+        let readme = dir.path().join("README");
+        fs::write(&readme, "Read me first").unwrap();
+        let page = dir.path().join("index");
+        fs::write(&page, "<!DOCTYPE html><title>Home</title><p>Welcome</p>").unwrap();
+        let script = dir.path().join("build");
+        fs::write(&script, "#!/usr/bin/env python3\n# Builds it\nmain()\n").unwrap();
+
+        assert_eq!(extract(&readme).unwrap().body(), "Read me first");
+        assert_eq!(extract(&page).unwrap().field(TITLE), Some("Home"));
+        assert_eq!(extract(&script).unwrap().field(IDENTIFIER), Some("main"));
+    }
+
+    #[test]
+    fn test_extract_binary_file() {
+        let dir = tempdir().unwrap();
+        let file_path = dir.path().join("image.txt");
+        fs::write(&file_path, b"\x89PNG\r\n\x1a\n\x00\x00\x00\rIHDR").unwrap();
+
+        let result = extract(&file_path);
+        assert!(result.is_err());
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn test_extract_latin1_file() {
+        let dir = tempdir().unwrap();
+        let file_path = dir.path().join("notes.txt");
+        fs::write(&file_path, b"Caf\xe9 cr\xe8me").unwrap();
+
+        let content = extract(&file_path).unwrap();
+        assert_eq!(content.body(), "Café crème");
+    }
+
+    #[test]
+    fn test_extract_nonexistent_file() {
+        let file_path = PathBuf::from("nonexistent.txt");
+        let result = extract(&file_path);
+        assert!(result.is_err());
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::NotFound);
+    }
+}
@@ -0,0 +1,200 @@
+use lopdf::{decode_text_string, Document};
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::panic;
+
+use super::Content;
+use crate::ranker::{AUTHOR, TITLE};
+
+/// Why a PDF could not be read
+#[derive(Debug)]
+pub enum PdfError {
+    /// The PDF can't be opened without a password
+    Encrypted,
+    /// The file isn't a PDF that can be parsed, or its content is damaged
+    Malformed(String),
+}
+
+impl fmt::Display for PdfError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PdfError::Encrypted => write!(f, "the PDF is encrypted"),
+            PdfError::Malformed(reason) => write!(f, "malformed PDF: {}", reason),
+        }
+    }
+}
+
+impl Error for PdfError {}
+
+impl From<PdfError> for io::Error {
+    fn from(e: PdfError) -> Self {
+        io::Error::new(io::ErrorKind::InvalidData, e)
+    }
+}
+
+/// Pulls the text of every page out of a PDF, in page order, along with the
+/// title and author from its metadata
+pub fn extract(bytes: &[u8]) -> Result<Content, PdfError> {
+    // The parser can panic on damaged files, which shouldn't take the whole run down
+    panic::catch_unwind(|| extract_document(bytes))
+        .unwrap_or_else(|_| Err(PdfError::Malformed("the parser failed".to_string())))
+}
+
+fn extract_document(bytes: &[u8]) -> Result<Content, PdfError> {
+    let doc = Document::load_mem(bytes).map_err(|e| match e {
+        lopdf::Error::Decryption(_) => PdfError::Encrypted,
+        e => PdfError::Malformed(e.to_string()),
+    })?;
+
+    // Files that only restrict permissions open with an empty password
+    if doc.is_encrypted() && doc.authenticate_password("").is_err() {
+        return Err(PdfError::Encrypted);
+    }
+
+    let mut content = Content::default();
+    if let Some(title) = info(&doc, b"Title") {
+        content.push(TITLE, &title);
+    }
+    if let Some(author) = info(&doc, b"Author") {
+        content.push(AUTHOR, &author);
+    }
+
+    for page in doc.get_pages().into_keys() {
+        let text = doc
+            .extract_text(&[page])
+            .map_err(|e| PdfError::Malformed(format!("page {}: {}", page, e)))?;
+        content.push_page(text.trim_end());
+    }
+
+    Ok(content)
+}
+
+/// A text entry of the document information dictionary
+fn info(doc: &Document, key: &[u8]) -> Option<String> {
+    let info = doc.trailer.get(b"Info").ok()?;
+    let (_, info) = doc.dereference(info).ok()?;
+    let value = info.as_dict().ok()?.get(key).ok()?;
+
+    decode_text_string(value).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ranker::BODY;
+    use lopdf::content::{Content as PageContent, Operation};
+    use lopdf::{dictionary, EncryptionState, EncryptionVersion, Object, Permissions, Stream};
+
+    /// Builds a PDF with one page per string, and a title and author
+    fn build(pages: &[&str]) -> Document {
+        let mut doc = Document::with_version("1.5");
+        let pages_id = doc.new_object_id();
+        let font_id = doc.add_object(dictionary! {
+            "Type" => "Font",
+            "Subtype" => "Type1",
+            "BaseFont" => "Courier",
+        });
+        let resources_id = doc.add_object(dictionary! {
+            "Font" => dictionary! { "F1" => font_id },
+        });
+
+        let mut kids = Vec::new();
+        for text in pages {
+            let content = PageContent {
+                operations: vec![
+                    Operation::new("BT", vec![]),
+                    Operation::new("Tf", vec!["F1".into(), 12.into()]),
+                    Operation::new("Td", vec![100.into(), 600.into()]),
+                    Operation::new("Tj", vec![Object::string_literal(*text)]),
+                    Operation::new("ET", vec![]),
+                ],
+            };
+            let content_id = doc.add_object(Stream::new(dictionary! {}, content.encode().unwrap()));
+            let page_id = doc.add_object(dictionary! {
+                "Type" => "Page",
+                "Parent" => pages_id,
+                "Contents" => content_id,
+            });
+            kids.push(page_id.into());
+        }
+
+        doc.objects.insert(
+            pages_id,
+            Object::Dictionary(dictionary! {
+                "Type" => "Pages",
+                "Count" => kids.len() as i64,
+                "Kids" => kids,
+                "Resources" => resources_id,
+                "MediaBox" => vec![0.into(), 0.into(), 595.into(), 842.into()],
+            }),
+        );
+        let catalog_id = doc.add_object(dictionary! {
+            "Type" => "Catalog",
+            "Pages" => pages_id,
+        });
+        let info_id = doc.add_object(dictionary! {
+            "Title" => Object::string_literal("Design Spec"),
+            "Author" => Object::string_literal("Ada Lovelace"),
+        });
+        doc.trailer.set("Root", catalog_id);
+        doc.trailer.set("Info", info_id);
+
+        doc
+    }
+
+    fn save(doc: &mut Document) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        doc.save_to(&mut bytes).unwrap();
+        bytes
+    }
+
+    /// Should keep each page's text separately, plus the metadata
+    #[test]
+    fn pages_and_metadata() {
+        let bytes = save(&mut build(&["First page", "Second page"]));
+
+        let content = extract(&bytes).unwrap();
+
+        assert_eq!(content.field(TITLE), Some("Design Spec"));
+        assert_eq!(content.field(AUTHOR), Some("Ada Lovelace"));
+        let pages: Vec<_> = content.pages().map(str::trim).collect();
+        assert_eq!(pages, vec!["First page", "Second page"]);
+        assert!(content.field(BODY).unwrap().contains("Second page"));
+    }
+
+    /// Damaged files should be reported, not panic
+    #[test]
+    fn malformed() {
+        let result = extract(b"%PDF-1.7\nthis is not really a pdf");
+
+        assert!(matches!(result, Err(PdfError::Malformed(_))));
+    }
+
+    /// Files that need a password should say so
+    #[test]
+    fn encrypted() {
+        let mut doc = build(&["Secret"]);
+        doc.trailer.set(
+            "ID",
+            vec![
+                Object::string_literal("0123456789abcdef"),
+                Object::string_literal("0123456789abcdef"),
+            ],
+        );
+        let state = EncryptionState::try_from(EncryptionVersion::V2 {
+            document: &doc,
+            owner_password: "owner",
+            user_password: "user",
+            key_length: 128,
+            permissions: Permissions::all(),
+        })
+        .unwrap();
+        doc.encrypt(&state).unwrap();
+        let bytes = save(&mut doc);
+
+        let result = extract(&bytes);
+
+        assert!(matches!(result, Err(PdfError::Encrypted)), "{:?}", result);
+    }
+}
@@ -0,0 +1,305 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::HashSet;
+use std::io;
+use std::path::Path;
+
+use super::Content;
+use crate::ranker::{BODY, TITLE};
+
+/// Which fields of a record hold its id, title and body. Nested JSON fields
+/// are named with dots, like `fields.summary`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecordFields {
+    pub id: String,
+    pub title: Option<String>,
+    /// The fields that make up the body, or every field other than the id
+    /// and title when empty
+    #[serde(default)]
+    pub body: Vec<String>,
+}
+
+impl Default for RecordFields {
+    fn default() -> Self {
+        Self {
+            id: "id".to_string(),
+            title: Some("title".to_string()),
+            body: Vec::new(),
+        }
+    }
+}
+
+/// One record of a structured file, ready to be indexed as a document
+#[derive(Debug, PartialEq)]
+pub struct Record {
+    /// The record's id, or its position in the file, from 1, if it has none.
+    /// Ids already taken by an earlier record get their position added, as
+    /// in `T-1#3`, so every record can be found again.
+    pub id: String,
+    pub content: Content,
+}
+
+/// Splits a JSON, JSONL or CSV file into records.
+///
+/// A JSON file may hold an array of objects or a single object, and a JSONL
+/// file holds one object per line. The first row of a CSV file names the
+/// fields of every other row.
+pub fn extract(path: &Path, text: &str, fields: &RecordFields) -> io::Result<Vec<Record>> {
+    let objects = match path.extension().and_then(|ext| ext.to_str()) {
+        Some("json") => match parse_json(text)? {
+            Value::Array(items) => items
+                .into_iter()
+                .enumerate()
+                .map(|(i, item)| object(item, i + 1))
+                .collect::<io::Result<_>>()?,
+            item => vec![object(item, 1)?],
+        },
+        Some("jsonl" | "ndjson") => text
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(i, line)| object(parse_json(line)?, i + 1))
+            .collect::<io::Result<_>>()?,
+        Some("csv") => csv_objects(text),
+        _ => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Records must be in a JSON, JSONL or CSV file",
+            ))
+        }
+    };
+
+    let mut ids = HashSet::new();
+    Ok(objects
+        .iter()
+        .enumerate()
+        .map(|(i, object)| {
+            let mut record = record(object, i + 1, fields);
+            if !ids.insert(record.id.clone()) {
+                record.id = format!("{}#{}", record.id, i + 1);
+                ids.insert(record.id.clone());
+            }
+            record
+        })
+        .collect())
+}
+
+fn record(object: &Map<String, Value>, position: usize, fields: &RecordFields) -> Record {
+    let id = lookup(object, &fields.id)
+        .map(text)
+        .filter(|id| !id.is_empty())
+        .unwrap_or_else(|| position.to_string());
+
+    let mut content = Content::default();
+    if let Some(title) = fields.title.as_ref().and_then(|name| lookup(object, name)) {
+        content.push(TITLE, &text(title));
+    }
+
+    if fields.body.is_empty() {
+        let skipped = |name: &String| *name == fields.id || Some(name) == fields.title.as_ref();
+        for (_, value) in object.iter().filter(|(name, _)| !skipped(name)) {
+            content.push(BODY, &text(value));
+        }
+    } else {
+        for value in fields.body.iter().filter_map(|name| lookup(object, name)) {
+            content.push(BODY, &text(value));
+        }
+    }
+
+    Record { id, content }
+}
+
+/// A field by name, or by a dotted path into nested objects
+fn lookup<'a>(object: &'a Map<String, Value>, name: &str) -> Option<&'a Value> {
+    if let Some(value) = object.get(name) {
+        return Some(value);
+    }
+
+    let mut parts = name.split('.');
+    let mut value = object.get(parts.next()?)?;
+    for part in parts {
+        value = value.as_object()?.get(part)?;
+    }
+    Some(value)
+}
+
+/// The text of a value, with the values inside arrays and objects on lines
+/// of their own
+fn text(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(s) => s.clone(),
+        Value::Array(items) => join(items.iter()),
+        Value::Object(map) => join(map.values()),
+        other => other.to_string(),
+    }
+}
+
+fn join<'a>(values: impl Iterator<Item = &'a Value>) -> String {
+    values
+        .map(text)
+        .filter(|text| !text.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn parse_json(text: &str) -> io::Result<Value> {
+    serde_json::from_str(text).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+fn object(value: Value, position: usize) -> io::Result<Map<String, Value>> {
+    match value {
+        Value::Object(map) => Ok(map),
+        _ => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("record {} is not an object", position),
+        )),
+    }
+}
+
+/// Rows after the header, as objects keyed by the header's names
+fn csv_objects(text: &str) -> Vec<Map<String, Value>> {
+    let mut rows = parse_csv(text).into_iter();
+    let Some(header) = rows.next() else {
+        return Vec::new();
+    };
+
+    rows.map(|row| {
+        header
+            .iter()
+            .cloned()
+            .zip(row.into_iter().map(Value::String))
+            .collect()
+    })
+    .collect()
+}
+
+/// Parses RFC 4180 CSV: comma separated fields, which may be quoted to hold
+/// commas, line breaks and doubled quotes. Blank lines are skipped.
+fn parse_csv(text: &str) -> Vec<Vec<String>> {
+    let mut rows = Vec::new();
+    let mut row = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if quoted {
+            match c {
+                '"' if chars.next_if_eq(&'"').is_some() => field.push('"'),
+                '"' => quoted = false,
+                c => field.push(c),
+            }
+            continue;
+        }
+
+        match c {
+            '"' => quoted = true,
+            ',' => row.push(std::mem::take(&mut field)),
+            '\r' if chars.peek() == Some(&'\n') => {}
+            '\n' | '\r' => {
+                row.push(std::mem::take(&mut field));
+                rows.push(std::mem::take(&mut row));
+            }
+            c => field.push(c),
+        }
+    }
+    if !field.is_empty() || !row.is_empty() {
+        row.push(field);
+        rows.push(row);
+    }
+
+    rows.retain(|row| row.len() > 1 || row.first().is_some_and(|f| !f.is_empty()));
+    rows
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ids(records: &[Record]) -> Vec<&str> {
+        records.iter().map(|r| r.id.as_str()).collect()
+    }
+
+    #[test]
+    fn jsonl() {
+        let records = extract(
+            Path::new("tickets.jsonl"),
+            "{\"id\": \"T-1\", \"title\": \"Crash\", \"body\": \"It crashed\", \"tags\": [\"ui\"]}\n\
+             \n\
+             {\"id\": 2, \"title\": \"Slow\", \"body\": \"It is slow\"}\n",
+            &RecordFields::default(),
+        )
+        .unwrap();
+
+        assert_eq!(ids(&records), vec!["T-1", "2"]);
+        assert_eq!(records[0].content.field(TITLE), Some("Crash"));
+        assert_eq!(records[0].content.body(), "It crashed\nui");
+    }
+
+    /// Fields should be found by dotted paths, and missing ids fall back to
+    /// the record's position
+    #[test]
+    fn json_nested_fields() {
+        let fields = RecordFields {
+            id: "key".to_string(),
+            title: Some("fields.summary".to_string()),
+            body: vec!["fields.description".to_string()],
+        };
+
+        let records = extract(
+            Path::new("export.json"),
+            r#"[
+                {"key": "WIKI-7", "fields": {"summary": "Setup", "description": "Install it", "votes": 3}},
+                {"fields": {"summary": "Untitled"}}
+            ]"#,
+            &fields,
+        )
+        .unwrap();
+
+        assert_eq!(ids(&records), vec!["WIKI-7", "2"]);
+        assert_eq!(records[0].content.field(TITLE), Some("Setup"));
+        assert_eq!(records[0].content.body(), "Install it");
+        assert_eq!(records[1].content.body(), "");
+    }
+
+    #[test]
+    fn csv() {
+        let records = extract(
+            Path::new("sheet.csv"),
+            "id,title,notes\r\n1,\"Budget, 2024\",\"Line one\nline \"\"two\"\"\"\r\n\r\n2,Plan,\n",
+            &RecordFields::default(),
+        )
+        .unwrap();
+
+        assert_eq!(ids(&records), vec!["1", "2"]);
+        assert_eq!(records[0].content.field(TITLE), Some("Budget, 2024"));
+        assert_eq!(records[0].content.body(), "Line one\nline \"two\"");
+        assert_eq!(records[1].content.body(), "");
+    }
+
+    /// Repeated ids should be made unique with the record's position
+    #[test]
+    fn duplicate_ids() {
+        let records = extract(
+            Path::new("tickets.jsonl"),
+            "{\"id\": \"T-1\"}\n{\"id\": \"T-1\"}\n{}\n{\"id\": \"3\"}\n",
+            &RecordFields::default(),
+        )
+        .unwrap();
+
+        assert_eq!(ids(&records), vec!["T-1", "T-1#2", "3", "3#4"]);
+    }
+
+    #[test]
+    fn errors() {
+        let fields = RecordFields::default();
+
+        let err = extract(Path::new("a.jsonl"), "{\"id\": 1}\n[1]\n", &fields).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(err.to_string(), "record 2 is not an object");
+
+        let err = extract(Path::new("a.txt"), "text", &fields).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+}
@@ -0,0 +1,225 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+
+use super::sniff;
+use super::{Content, Extract, Html, Markdown, Pdf, PlainText, Python, Rust};
+
+/// The extractors for every known format, looked up by file extension and
+/// by the MIME type sniffed from a file's content.
+///
+/// `Registry::default()` knows the built-in formats. Downstream crates can
+/// add their own, or replace a built-in, with [`Registry::register`].
+#[derive(Clone)]
+pub struct Registry {
+    by_extension: HashMap<String, Arc<dyn Extract>>,
+    by_mime_type: HashMap<String, Arc<dyn Extract>>,
+}
+
+impl Registry {
+    /// A registry without any formats
+    pub fn empty() -> Self {
+        Self {
+            by_extension: HashMap::new(),
+            by_mime_type: HashMap::new(),
+        }
+    }
+
+    /// Registers an extractor for files with any of `extensions`, given
+    /// without the dot, or whose content sniffs as any of `mime_types`.
+    /// Replaces whatever was registered for them before.
+    pub fn register<E>(
+        &mut self,
+        extensions: &[&str],
+        mime_types: &[&str],
+        extractor: E,
+    ) -> &mut Self
+    where
+        E: Extract + 'static,
+    {
+        let extractor: Arc<dyn Extract> = Arc::new(extractor);
+        for ext in extensions {
+            self.by_extension
+                .insert(ext.to_ascii_lowercase(), Arc::clone(&extractor));
+        }
+        for mime in mime_types {
+            self.by_mime_type
+                .insert(mime.to_ascii_lowercase(), Arc::clone(&extractor));
+        }
+
+        self
+    }
+
+    /// The extractor for a file extension, given without the dot
+    pub fn for_extension(&self, ext: &str) -> Option<&dyn Extract> {
+        self.by_extension
+            .get(&ext.to_ascii_lowercase())
+            .map(|e| e.as_ref())
+    }
+
+    /// The extractor for a MIME type
+    pub fn for_mime_type(&self, mime: &str) -> Option<&dyn Extract> {
+        self.by_mime_type
+            .get(&mime.to_ascii_lowercase())
+            .map(|e| e.as_ref())
+    }
+
+    /// The extractor for a file, chosen from its content as well as its name.
+    ///
+    /// For text, the extension decides, so `notes.md` is read as Markdown;
+    /// files without a registered extension fall back to the sniffed type.
+    /// For anything else the sniffed type wins, so a PDF is read as one
+    /// whatever it is called.
+    pub fn find(&self, path: &Path, bytes: &[u8]) -> Option<&dyn Extract> {
+        self.find_as(path, sniff::mime_type(bytes))
+    }
+
+    /// Like [`Registry::find`], for a file whose content was already
+    /// sniffed as `mime`
+    pub fn find_as(&self, path: &Path, mime: &str) -> Option<&dyn Extract> {
+        let by_extension = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .and_then(|ext| self.for_extension(ext));
+        let by_mime_type = self.for_mime_type(mime);
+
+        if mime.starts_with("text/") {
+            by_extension.or(by_mime_type)
+        } else {
+            by_mime_type.or(by_extension)
+        }
+    }
+
+    /// Extracts a file's content with the extractor [`Registry::find`]
+    /// chooses. Formats nothing is registered for are rejected with
+    /// `InvalidInput`.
+    pub fn extract(&self, path: &Path, bytes: &[u8]) -> io::Result<Content> {
+        self.extract_as(path, bytes, sniff::mime_type(bytes))
+    }
+
+    /// Like [`Registry::extract`], for a file whose content was already
+    /// sniffed as `mime`
+    pub fn extract_as(&self, path: &Path, bytes: &[u8], mime: &str) -> io::Result<Content> {
+        if let Some(extractor) = self.find_as(path, mime) {
+            return extractor.extract(path, bytes);
+        }
+
+        match mime {
+            sniff::BINARY => Err(io::Error::new(io::ErrorKind::InvalidInput, "Binary file")),
+            mime => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Unsupported format: {}", mime),
+            )),
+        }
+    }
+
+    /// Reads a file and extracts its content
+    pub fn extract_file(&self, path: &Path) -> io::Result<Content> {
+        self.extract(path, &fs::read(path)?)
+    }
+}
+
+impl Default for Registry {
+    fn default() -> Self {
+        let mut registry = Self::empty();
+        registry
+            .register(&["txt"], &["text/plain"], PlainText)
+            .register(&["md", "markdown"], &["text/markdown"], Markdown)
+            .register(&["html", "htm"], &["text/html"], Html)
+            .register(&["rs"], &["text/x-rust"], Rust)
+            .register(&["py", "pyi"], &["text/x-python"], Python)
+            .register(&["pdf"], &["application/pdf"], Pdf);
+
+        registry
+    }
+}
+
+impl fmt::Debug for Registry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut extensions: Vec<_> = self.by_extension.keys().collect();
+        extensions.sort();
+        let mut mime_types: Vec<_> = self.by_mime_type.keys().collect();
+        mime_types.sort();
+
+        f.debug_struct("Registry")
+            .field("extensions", &extensions)
+            .field("mime_types", &mime_types)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::extract::decode_text;
+    use crate::ranker::{BODY, TITLE};
+
+    /// A made up format whose first line is the title
+    fn wiki(_path: &Path, bytes: &[u8]) -> io::Result<Content> {
+        let text = decode_text(bytes)?;
+        let (title, body) = text.split_once('\n').unwrap_or((&text, ""));
+
+        let mut content = Content::text(body);
+        content.push(TITLE, title);
+        Ok(content)
+    }
+
+    /// Downstream formats should be found by extension
+    #[test]
+    fn register_extension() {
+        let mut registry = Registry::default();
+        registry.register(&["wiki"], &[], wiki);
+
+        let content = registry
+            .extract(Path::new("Home.WIKI"), b"Welcome\nStart here")
+            .unwrap();
+
+        assert_eq!(content.field(TITLE), Some("Welcome"));
+        assert_eq!(content.field(BODY), Some("Start here"));
+    }
+
+    /// Registering an extension again should replace the built-in
+    #[test]
+    fn replace_builtin() {
+        let mut registry = Registry::default();
+        registry.register(&["txt"], &[], wiki);
+
+        let content = registry
+            .extract(Path::new("notes.txt"), b"Title\nBody")
+            .unwrap();
+
+        assert_eq!(content.field(TITLE), Some("Title"));
+    }
+
+    /// Binary content should be matched by type, not by a misleading name
+    #[test]
+    fn content_beats_extension() {
+        let mut registry = Registry::default();
+        let png = b"\x89PNG\r\n\x1a\n\x00\x00\x00\rIHDR";
+
+        let err = registry.extract(Path::new("image.txt"), png).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+
+        registry.register(&[], &["image/png"], |_: &Path, _: &[u8]| {
+            Ok(Content::text("a picture"))
+        });
+        let content = registry.extract(Path::new("image.txt"), png).unwrap();
+        assert_eq!(content.body(), "a picture");
+    }
+
+    /// Formats nobody registered should be rejected, not failed
+    #[test]
+    fn unsupported() {
+        let registry = Registry::empty();
+
+        let err = registry
+            .extract(Path::new("notes.txt"), b"text")
+            .unwrap_err();
+
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        assert_eq!(err.to_string(), "Unsupported format: text/plain");
+    }
+}
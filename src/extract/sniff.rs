@@ -0,0 +1,169 @@
+/// Signatures of common formats and their MIME types. Binary formats are
+/// listed even though they can't be extracted, as they may not give
+/// themselves away in the bytes checked for control characters.
+const MAGIC: &[(&[u8], &str)] = &[
+    (b"%PDF-", "application/pdf"),
+    (b"\x89PNG\r\n\x1a\n", "image/png"),
+    (b"\xff\xd8\xff", "image/jpeg"),
+    (b"GIF87a", "image/gif"),
+    (b"GIF89a", "image/gif"),
+    (b"PK\x03\x04", "application/zip"),
+    (b"\x1f\x8b", "application/gzip"),
+    (b"BZh", "application/x-bzip2"),
+    (b"\xfd7zXZ\x00", "application/x-xz"),
+    (b"7z\xbc\xaf\x27\x1c", "application/x-7z-compressed"),
+    (b"\x28\xb5\x2f\xfd", "application/zstd"),
+    (b"Rar!\x1a\x07", "application/vnd.rar"),
+    (b"\x7fELF", "application/x-executable"),
+    (b"\xcf\xfa\xed\xfe", "application/x-mach-binary"),
+    (b"\x00asm", "application/wasm"),
+    (b"SQLite format 3\x00", "application/vnd.sqlite3"),
+    (b"OggS", "audio/ogg"),
+    (b"ID3", "audio/mpeg"),
+    (b"fLaC", "audio/flac"),
+];
+
+/// The type of anything that is neither text nor a known format
+pub const BINARY: &str = "application/octet-stream";
+
+/// Only the start of a file is checked for control characters, or decoded
+/// to tell what kind of text it is
+const SAMPLE_LEN: usize = 8192;
+
+/// Decides what a file is from its content, as a MIME type.
+///
+/// Known signatures are checked first, then anything that decodes as text
+/// is `text/plain`, unless its first line shows it is an HTML document or a
+/// Python script. Everything else is `application/octet-stream`.
+pub fn mime_type(bytes: &[u8]) -> &'static str {
+    if let Some((_, mime)) = MAGIC.iter().find(|(magic, _)| bytes.starts_with(magic)) {
+        return mime;
+    }
+
+    let Some(text) = decode(&bytes[..bytes.len().min(SAMPLE_LEN)]) else {
+        return BINARY;
+    };
+    let first_line = text
+        .trim_start()
+        .lines()
+        .next()
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+
+    if first_line.starts_with("<!doctype html") || first_line.starts_with("<html") {
+        "text/html"
+    } else if first_line.starts_with("#!") && first_line.contains("python") {
+        "text/x-python"
+    } else {
+        "text/plain"
+    }
+}
+
+/// Decodes text, or returns `None` if the bytes aren't text.
+///
+/// Anything with a byte order mark is decoded as UTF-8 or UTF-16. Without
+/// one, bytes with NULs or lots of control characters are binary, valid
+/// UTF-8 is taken as it is, and the rest is decoded as Latin-1.
+pub fn decode(bytes: &[u8]) -> Option<String> {
+    if let Some(utf8) = bytes.strip_prefix(b"\xef\xbb\xbf") {
+        return Some(String::from_utf8_lossy(utf8).into_owned());
+    }
+    if let Some(utf16) = bytes.strip_prefix(b"\xff\xfe") {
+        return Some(decode_utf16(utf16, u16::from_le_bytes));
+    }
+    if let Some(utf16) = bytes.strip_prefix(b"\xfe\xff") {
+        return Some(decode_utf16(utf16, u16::from_be_bytes));
+    }
+
+    if is_binary(&bytes[..bytes.len().min(SAMPLE_LEN)]) {
+        return None;
+    }
+
+    match std::str::from_utf8(bytes) {
+        Ok(text) => Some(text.to_string()),
+        // Latin-1 maps every byte straight to the code point of the same value
+        Err(_) => Some(bytes.iter().map(|&b| char::from(b)).collect()),
+    }
+}
+
+/// Whether a sample has a NUL byte, or more than one in ten bytes are
+/// control characters that text doesn't use
+fn is_binary(sample: &[u8]) -> bool {
+    if sample.contains(&0) {
+        return true;
+    }
+
+    let control = sample
+        .iter()
+        .filter(|&&b| b < 0x20 && !matches!(b, b'\t' | b'\n' | b'\r' | 0x0c | 0x1b))
+        .count();
+    control * 10 > sample.len()
+}
+
+fn decode_utf16(bytes: &[u8], unit: fn([u8; 2]) -> u16) -> String {
+    let units = bytes.chunks_exact(2).map(|pair| unit([pair[0], pair[1]]));
+
+    char::decode_utf16(units)
+        .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn signatures() {
+        assert_eq!(
+            mime_type(b"%PDF-1.7\n%\xe2\xe3\xcf\xd3\n"),
+            "application/pdf"
+        );
+        assert_eq!(
+            mime_type(b"\x89PNG\r\n\x1a\n\x00\x00\x00\rIHDR"),
+            "image/png"
+        );
+        assert_eq!(mime_type(b"PK\x03\x04\x14\x00"), "application/zip");
+    }
+
+    /// Should tell text formats apart by their first line
+    #[test]
+    fn text_types() {
+        assert_eq!(mime_type(b"\n  <!DOCTYPE html>\n<p>Hi</p>"), "text/html");
+        assert_eq!(mime_type(b"#!/usr/bin/env python3\n"), "text/x-python");
+        assert_eq!(mime_type(b"#!/bin/sh\n"), "text/plain");
+        assert_eq!(mime_type(b""), "text/plain");
+
+        // Only the start is looked at, even when it cuts a character in two
+        let mut html = "<!DOCTYPE html>\n".to_string();
+        html.push_str(&"é".repeat(SAMPLE_LEN));
+        assert_eq!(mime_type(&html.as_bytes()[..SAMPLE_LEN + 1]), "text/html");
+    }
+
+    /// Should tell binary data from text by its control characters
+    #[test]
+    fn binary() {
+        assert_eq!(mime_type(b"plain\x00text"), BINARY);
+        assert_eq!(decode(b"\x01\x02\x03\x04abc"), None);
+        assert_eq!(
+            decode(b"tab\tand\r\nnewline\x0c").as_deref(),
+            Some("tab\tand\r\nnewline\x0c")
+        );
+    }
+
+    #[test]
+    fn encodings() {
+        let cases: [&[u8]; 5] = [
+            "café".as_bytes(),
+            b"\xef\xbb\xbfcaf\xc3\xa9",
+            b"caf\xe9",
+            b"\xff\xfec\x00a\x00f\x00\xe9\x00",
+            b"\xfe\xff\x00c\x00a\x00f\x00\xe9",
+        ];
+        for bytes in cases {
+            assert_eq!(decode(bytes).as_deref(), Some("café"), "{:?}", bytes);
+        }
+
+        // A surrogate pair
+        assert_eq!(decode(b"\xff\xfe\x3d\xd8\x00\xde").as_deref(), Some("😀"));
+    }
+}
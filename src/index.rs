@@ -0,0 +1,249 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::ranker::BODY;
+
+/// Compact identifier of a document in the model
+pub type DocId = u32;
+
+/// The occurrences of a term within one document
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Posting {
+    pub doc: DocId,
+    /// The number of times the term appears in the document
+    pub freq: usize,
+    /// Token offsets of each occurrence, in ascending order
+    pub positions: Vec<u32>,
+}
+
+/// Unused positions left between the fields of a document, so phrases never
+/// span two fields and NEAR rarely does
+const FIELD_GAP: u32 = 100;
+
+/// The run of positions one field of a document takes up
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FieldRange {
+    pub name: String,
+    pub start: u32,
+    /// The number of positions in the field, up to and including its last term
+    pub len: u32,
+}
+
+impl FieldRange {
+    pub fn contains(&self, position: u32) -> bool {
+        position >= self.start && position < self.start + self.len
+    }
+}
+
+/// Maps each term to the documents containing it.
+/// Posting lists are kept sorted by document id.
+#[derive(Default, Debug, Serialize, Deserialize)]
+#[serde(from = "SavedIndex")]
+pub struct InvertedIndex {
+    postings: HashMap<String, Vec<Posting>>,
+    /// The distinct terms of each document, so removing one only touches
+    /// its own posting lists. Rebuilt from the postings on load.
+    #[serde(skip_serializing)]
+    terms: HashMap<DocId, Vec<String>>,
+}
+
+/// The part of the index that is saved
+#[derive(Deserialize)]
+struct SavedIndex {
+    postings: HashMap<String, Vec<Posting>>,
+}
+
+impl From<SavedIndex> for InvertedIndex {
+    fn from(saved: SavedIndex) -> Self {
+        let mut terms: HashMap<DocId, Vec<String>> = HashMap::new();
+        for (term, list) in &saved.postings {
+            for posting in list {
+                terms.entry(posting.doc).or_default().push(term.clone());
+            }
+        }
+
+        Self {
+            postings: saved.postings,
+            terms,
+        }
+    }
+}
+
+impl InvertedIndex {
+    /// Indexes a document given its terms in order, one per position.
+    /// Returns the number of terms.
+    pub fn insert<I, S>(&mut self, doc: DocId, terms: I) -> usize
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        let fields = self.insert_fields(doc, [(BODY, (0..).zip(terms))]);
+        fields[0].len as usize
+    }
+
+    /// Indexes a document made of several fields, each given its terms with
+    /// their positions in the field, in ascending order. Returns the
+    /// positions each field was given.
+    pub fn insert_fields<F, N, I, S>(&mut self, doc: DocId, fields: F) -> Vec<FieldRange>
+    where
+        F: IntoIterator<Item = (N, I)>,
+        N: Into<String>,
+        I: IntoIterator<Item = (u32, S)>,
+        S: Into<String>,
+    {
+        // Group the positions of each term first so every posting list is touched once
+        let mut positions: HashMap<String, Vec<u32>> = HashMap::new();
+        let mut ranges: Vec<FieldRange> = Vec::new();
+        for (name, terms) in fields {
+            let start = ranges.last().map_or(0, |r| r.start + r.len + FIELD_GAP);
+            let mut len = 0;
+            for (position, term) in terms {
+                // An identifier's parts may repeat, as in `Query_query`
+                let list = positions.entry(term.into()).or_default();
+                if list.last() != Some(&(start + position)) {
+                    list.push(start + position);
+                }
+                len = position + 1;
+            }
+
+            ranges.push(FieldRange {
+                name: name.into(),
+                start,
+                len,
+            });
+        }
+
+        // A document indexed again shouldn't keep terms it no longer has
+        self.remove(doc);
+        self.terms.insert(doc, positions.keys().cloned().collect());
+        for (term, positions) in positions {
+            let list = self.postings.entry(term).or_default();
+            let posting = Posting {
+                doc,
+                freq: positions.len(),
+                positions,
+            };
+
+            match list.binary_search_by_key(&doc, |p| p.doc) {
+                Ok(i) => list[i] = posting,
+                Err(i) => list.insert(i, posting),
+            }
+        }
+
+        ranges
+    }
+
+    /// Drops every posting of a document, and any term left without postings
+    pub fn remove(&mut self, doc: DocId) {
+        for term in self.terms.remove(&doc).unwrap_or_default() {
+            let Some(list) = self.postings.get_mut(&term) else {
+                continue;
+            };
+            if let Ok(i) = list.binary_search_by_key(&doc, |p| p.doc) {
+                list.remove(i);
+            }
+            if list.is_empty() {
+                self.postings.remove(&term);
+            }
+        }
+    }
+
+    /// The posting list of a term, empty if no document contains it
+    pub fn postings(&self, term: &str) -> &[Posting] {
+        self.postings.get(term).map_or(&[], Vec::as_slice)
+    }
+
+    /// The number of documents containing a term
+    pub fn df(&self, term: &str) -> usize {
+        self.postings(term).len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Should record frequencies and positions per document
+    #[test]
+    fn insert_positions() {
+        let mut index = InvertedIndex::default();
+
+        assert_eq!(index.insert(7, ["a", "b", "a"]), 3);
+        index.insert(3, ["b"]);
+
+        assert_eq!(
+            index.postings("a"),
+            &[Posting {
+                doc: 7,
+                freq: 2,
+                positions: vec![0, 2]
+            }]
+        );
+        let docs: Vec<_> = index.postings("b").iter().map(|p| p.doc).collect();
+        assert_eq!(docs, vec![3, 7]);
+        assert_eq!(index.df("missing"), 0);
+    }
+
+    /// Fields should get their own, non-adjacent, positions
+    #[test]
+    fn insert_fields() {
+        let mut index = InvertedIndex::default();
+
+        let fields = index.insert_fields(
+            0,
+            [
+                ("title", vec![(0, "a"), (1, "b")]),
+                ("body", vec![(0, "b")]),
+            ],
+        );
+
+        assert_eq!(fields[0].start, 0);
+        assert_eq!(fields[0].len, 2);
+        assert_eq!(fields[1].start, 2 + FIELD_GAP);
+        assert!(fields[1].contains(2 + FIELD_GAP));
+        assert!(!fields[0].contains(2));
+        assert_eq!(index.postings("b")[0].positions, vec![1, 2 + FIELD_GAP]);
+    }
+
+    /// Should drop terms no document contains anymore
+    #[test]
+    fn remove_document() {
+        let mut index = InvertedIndex::default();
+        index.insert(0, ["shared", "alpha"]);
+        index.insert(1, ["shared", "beta"]);
+
+        index.remove(0);
+
+        assert_eq!(index.df("shared"), 1);
+        assert_eq!(index.df("alpha"), 0);
+        assert!(!index.postings.contains_key("alpha"));
+    }
+
+    /// A loaded index should still know which terms each document has
+    #[test]
+    fn remove_after_load() {
+        let mut index = InvertedIndex::default();
+        index.insert(0, ["shared", "alpha"]);
+        index.insert(1, ["shared", "beta"]);
+
+        let json = serde_json::to_string(&index).unwrap();
+        assert!(!json.contains("\"terms\""));
+        let mut index: InvertedIndex = serde_json::from_str(&json).unwrap();
+        index.remove(1);
+
+        assert_eq!(index.df("shared"), 1);
+        assert!(!index.postings.contains_key("beta"));
+    }
+
+    /// Indexing a document again should drop the terms it no longer has
+    #[test]
+    fn reinsert_document() {
+        let mut index = InvertedIndex::default();
+        index.insert(0, ["alpha", "beta"]);
+
+        index.insert(0, ["beta"]);
+
+        assert_eq!(index.df("alpha"), 0);
+        assert_eq!(index.postings("beta")[0].positions, vec![0]);
+    }
+}
@@ -0,0 +1,248 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::OnceLock;
+
+use crate::snowball::StemmingAlgorithm;
+
+/// A language documents can be detected as, each with its own stemmer
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Language {
+    English,
+    German,
+    French,
+    Spanish,
+}
+
+impl Language {
+    pub const ALL: [Language; 4] = [
+        Language::English,
+        Language::German,
+        Language::French,
+        Language::Spanish,
+    ];
+
+    /// Looks a language up by English name or ISO 639-1 code
+    pub fn from_name(name: &str) -> Option<Language> {
+        match name.to_lowercase().as_str() {
+            "english" | "en" => Some(Language::English),
+            "german" | "de" => Some(Language::German),
+            "french" | "fr" => Some(Language::French),
+            "spanish" | "es" => Some(Language::Spanish),
+            _ => None,
+        }
+    }
+
+    /// The stemmer documents in this language are indexed with
+    pub fn stemmer(self) -> StemmingAlgorithm {
+        match self {
+            Language::English => StemmingAlgorithm::Porter2,
+            Language::German => StemmingAlgorithm::German,
+            Language::French => StemmingAlgorithm::French,
+            Language::Spanish => StemmingAlgorithm::Spanish,
+        }
+    }
+
+    /// Representative text the language's n-gram profile is built from
+    fn sample(self) -> &'static str {
+        match self {
+            Language::English => ENGLISH_SAMPLE,
+            Language::German => GERMAN_SAMPLE,
+            Language::French => FRENCH_SAMPLE,
+            Language::Spanish => SPANISH_SAMPLE,
+        }
+    }
+}
+
+impl fmt::Display for Language {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Language::English => write!(f, "english"),
+            Language::German => write!(f, "german"),
+            Language::French => write!(f, "french"),
+            Language::Spanish => write!(f, "spanish"),
+        }
+    }
+}
+
+/// Texts with fewer trigrams than this are too short to tell apart
+const MIN_TRIGRAMS: usize = 20;
+
+/// Only the start of long documents is looked at, which is plenty to decide
+const MAX_CHARS: usize = 10_000;
+
+/// Guesses the language of a text from its character trigrams.
+///
+/// Each language has a trigram profile built from a sample text. The text is
+/// scored against every profile as a naive Bayes classifier with add-one
+/// smoothing, and the most likely language wins. Returns `None` when the
+/// text is too short to judge.
+pub fn detect(text: &str) -> Option<Language> {
+    let text: String = text.chars().take(MAX_CHARS).collect();
+    let trigrams = trigrams(&text);
+    if trigrams.len() < MIN_TRIGRAMS {
+        return None;
+    }
+
+    profiles()
+        .iter()
+        .map(|profile| (profile.language, profile.log_likelihood(&trigrams)))
+        .max_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map(|(language, _)| language)
+}
+
+struct Profile {
+    language: Language,
+    counts: HashMap<String, usize>,
+    total: usize,
+}
+
+impl Profile {
+    fn new(language: Language) -> Self {
+        let mut counts = HashMap::new();
+        let mut total = 0;
+        for trigram in trigrams(language.sample()) {
+            *counts.entry(trigram).or_default() += 1;
+            total += 1;
+        }
+
+        Self {
+            language,
+            counts,
+            total,
+        }
+    }
+
+    fn log_likelihood(&self, trigrams: &[String]) -> f64 {
+        // One extra slot for every trigram this profile has never seen
+        let denominator = (self.total + self.counts.len() + 1) as f64;
+
+        trigrams
+            .iter()
+            .map(|t| {
+                let count = self.counts.get(t).copied().unwrap_or(0);
+                ((count + 1) as f64 / denominator).ln()
+            })
+            .sum()
+    }
+}
+
+fn profiles() -> &'static [Profile] {
+    static PROFILES: OnceLock<Vec<Profile>> = OnceLock::new();
+    PROFILES.get_or_init(|| Language::ALL.into_iter().map(Profile::new).collect())
+}
+
+/// Lowercased letter trigrams of each word, padded with spaces so word
+/// beginnings and endings count too
+fn trigrams(text: &str) -> Vec<String> {
+    let mut trigrams = Vec::new();
+
+    for word in text.split(|c: char| !c.is_alphabetic()) {
+        if word.is_empty() {
+            continue;
+        }
+
+        let padded: Vec<char> = format!(" {} ", word.to_lowercase()).chars().collect();
+        trigrams.extend(padded.windows(3).map(|w| w.iter().collect::<String>()));
+    }
+
+    trigrams
+}
+
+const ENGLISH_SAMPLE: &str = "\
+    The search engine reads every document in the folder and builds an index of \
+    the words it contains. When you run a query, it looks up each word in the index \
+    and ranks the documents by how often the words appear and how rare they are \
+    across the whole collection. This makes it possible to find the right file \
+    quickly, even when there are thousands of them. You can add new files at any \
+    time, remove the ones you no longer need, and keep the index up to date with a \
+    single command. The documentation explains how to install the program, which \
+    options are available and what each of them does. It also describes the basics \
+    of text analysis and why stemming matters for the quality of the results.";
+
+const GERMAN_SAMPLE: &str = "\
+    Die Suchmaschine liest jedes Dokument im Ordner und erstellt einen Index der \
+    Wörter, die darin vorkommen. Wenn Sie eine Anfrage stellen, sucht sie jedes \
+    Wort im Index und ordnet die Dokumente danach, wie oft die Wörter erscheinen \
+    und wie selten sie in der gesamten Sammlung sind. Dadurch lässt sich die \
+    richtige Datei schnell finden, auch wenn es Tausende davon gibt. Sie können \
+    jederzeit neue Dateien hinzufügen, nicht mehr benötigte entfernen und den \
+    Index mit einem einzigen Befehl aktuell halten. Die Dokumentation erklärt, wie \
+    das Programm installiert wird, welche Optionen es gibt und was jede von ihnen \
+    bewirkt. Außerdem werden die Grundlagen der Textanalyse und die Bedeutung der \
+    Stammformbildung für die Qualität der Ergebnisse beschrieben.";
+
+const FRENCH_SAMPLE: &str = "\
+    Le moteur de recherche lit chaque document du dossier et construit un index \
+    des mots qu'il contient. Lorsque vous lancez une requête, il cherche chaque mot \
+    dans l'index et classe les documents selon la fréquence des mots et leur rareté \
+    dans l'ensemble de la collection. Il est ainsi possible de trouver rapidement \
+    le bon fichier, même lorsqu'il y en a des milliers. Vous pouvez ajouter de \
+    nouveaux fichiers à tout moment, supprimer ceux dont vous n'avez plus besoin et \
+    tenir l'index à jour avec une seule commande. La documentation explique comment \
+    installer le programme, quelles sont les options disponibles et ce que fait \
+    chacune d'entre elles. Elle décrit aussi les bases de l'analyse du texte et le \
+    rôle de la racinisation pour la qualité des résultats.";
+
+const SPANISH_SAMPLE: &str = "\
+    El motor de búsqueda lee cada documento de la carpeta y construye un índice de \
+    las palabras que contiene. Cuando usted hace una consulta, busca cada palabra \
+    en el índice y ordena los documentos según la frecuencia con que aparecen las \
+    palabras y lo raras que son en toda la colección. Así es posible encontrar \
+    rápidamente el archivo correcto, incluso cuando hay miles de ellos. Puede \
+    añadir nuevos archivos en cualquier momento, eliminar los que ya no necesita y \
+    mantener el índice actualizado con un solo comando. La documentación explica \
+    cómo instalar el programa, qué opciones están disponibles y qué hace cada una \
+    de ellas. También describe los fundamentos del análisis de texto y la \
+    importancia de la lematización para la calidad de los resultados.";
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_languages() {
+        let cases = [
+            (
+                "Before you start, make sure that the configuration file is in your \
+                 home directory and that you have permission to write to it.",
+                Language::English,
+            ),
+            (
+                "Bevor Sie beginnen, stellen Sie sicher, dass sich die \
+                 Konfigurationsdatei in Ihrem Heimatverzeichnis befindet und dass \
+                 Sie Schreibrechte dafür haben.",
+                Language::German,
+            ),
+            (
+                "Avant de commencer, vérifiez que le fichier de configuration se \
+                 trouve dans votre répertoire personnel et que vous avez le droit \
+                 d'y écrire.",
+                Language::French,
+            ),
+            (
+                "Antes de empezar, asegúrese de que el archivo de configuración está \
+                 en su directorio personal y de que tiene permiso para escribir en él.",
+                Language::Spanish,
+            ),
+        ];
+
+        for (text, language) in cases {
+            assert_eq!(detect(text), Some(language), "{text}");
+        }
+    }
+
+    #[test]
+    fn short_text_is_undecided() {
+        assert_eq!(detect("hello"), None);
+        assert_eq!(detect(""), None);
+    }
+
+    #[test]
+    fn from_name() {
+        assert_eq!(Language::from_name("DE"), Some(Language::German));
+        assert_eq!(Language::from_name("spanish"), Some(Language::Spanish));
+        assert_eq!(Language::from_name("klingon"), None);
+    }
+}
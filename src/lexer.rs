@@ -1,5 +1,9 @@
+use serde::{Deserialize, Serialize};
+use std::ops::Range;
 use std::str::CharIndices;
 
+use unicode_normalization::char::is_combining_mark;
+
 // Inspired by the https://eli.thegreenplace.net/2022/rewriting-the-lexer-benchmark-in-rust/#footnote-1
 #[derive(Debug, PartialEq, Clone, Copy)]
 pub enum Token<'a> {
@@ -8,6 +12,116 @@ pub enum Token<'a> {
     Number(&'a str),
     Word(&'a str),
     Symbol(&'a str),
+    /// A run of Chinese, Japanese or Korean characters, which are written
+    /// without spaces between words
+    Cjk(&'a str),
+    /// A number with a fraction or thousands separators, like `3.14` or `1,000`
+    Decimal(&'a str),
+    /// A version like `v1.2` or `1.2.3`, or another dotted number like an
+    /// IPv4 address
+    Version(&'a str),
+    /// A hexadecimal literal like `0x1F`
+    Hex(&'a str),
+    /// An ISO 8601 date like `2024-06-01`
+    Date(&'a str),
+    Email(&'a str),
+    /// A URL with a scheme, like `https://example.com/path`
+    Url(&'a str),
+    /// A `#hashtag`
+    Hashtag(&'a str),
+}
+
+impl<'a> Token<'a> {
+    /// The text the token was read from, if it isn't `EOF` or `Error`
+    pub fn text(&self) -> Option<&'a str> {
+        match *self {
+            Token::EOF | Token::Error => None,
+            Token::Number(text)
+            | Token::Word(text)
+            | Token::Symbol(text)
+            | Token::Cjk(text)
+            | Token::Decimal(text)
+            | Token::Version(text)
+            | Token::Hex(text)
+            | Token::Date(text)
+            | Token::Email(text)
+            | Token::Url(text)
+            | Token::Hashtag(text) => Some(text),
+        }
+    }
+}
+
+/// Which structured tokens the lexer recognises whole, rather than breaking
+/// them into words, numbers and symbols. Every kind is recognised by default.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Recognizers {
+    #[serde(default)]
+    pub decimals: bool,
+    #[serde(default)]
+    pub versions: bool,
+    #[serde(default)]
+    pub hex: bool,
+    #[serde(default)]
+    pub dates: bool,
+    #[serde(default)]
+    pub emails: bool,
+    #[serde(default)]
+    pub urls: bool,
+    #[serde(default)]
+    pub hashtags: bool,
+}
+
+impl Recognizers {
+    pub const ALL: Self = Self {
+        decimals: true,
+        versions: true,
+        hex: true,
+        dates: true,
+        emails: true,
+        urls: true,
+        hashtags: true,
+    };
+
+    /// Plain words, numbers and symbols only
+    pub const NONE: Self = Self {
+        decimals: false,
+        versions: false,
+        hex: false,
+        dates: false,
+        emails: false,
+        urls: false,
+        hashtags: false,
+    };
+}
+
+impl Default for Recognizers {
+    fn default() -> Self {
+        Self::ALL
+    }
+}
+
+/// A token along with where it was found in the input
+#[derive(Debug, PartialEq, Clone)]
+pub struct Spanned<T> {
+    pub value: T,
+    /// The byte range of the input the token was read from
+    pub span: Range<usize>,
+    /// The line the token starts on, from 1
+    pub line: usize,
+    /// The column the token starts at, in characters from 1
+    pub column: usize,
+}
+
+impl<T> Spanned<T> {
+    /// The same position with a different value
+    pub fn map<U>(self, f: impl FnOnce(T) -> U) -> Spanned<U> {
+        Spanned {
+            value: f(self.value),
+            span: self.span,
+            line: self.line,
+            column: self.column,
+        }
+    }
 }
 
 /// A `Lexer` struct holds a reference to some content.
@@ -22,23 +136,60 @@ pub struct Lexer<'a> {
     c: char,
     // Offset in the input
     ci: usize,
+    // Line and column of c, from 1
+    line: usize,
+    column: usize,
+
+    recognizers: Recognizers,
 }
 
 impl<'a> Lexer<'a> {
+    /// A lexer that recognises every kind of structured token
     pub fn new(input: &'a str) -> Self {
+        Self::with_recognizers(input, Recognizers::ALL)
+    }
+
+    /// A lexer that only recognises the given kinds of structured token
+    pub fn with_recognizers(input: &'a str, recognizers: Recognizers) -> Self {
         let mut lex = Self {
             input,
             iter: input.char_indices(),
             c: '\x00', // null character
             ci: 0,
+            line: 1,
+            column: 0,
+            recognizers,
         };
 
         lex.scan_char();
         lex
     }
 
+    /// The next token with its position, or `None` at the end of the input
+    pub fn next_spanned(&mut self) -> Option<Spanned<Token<'a>>> {
+        self.skip_nontokens();
+        let (start, line, column) = (self.ci, self.line, self.column);
+
+        let value = match self.next_token() {
+            Token::EOF | Token::Error => return None,
+            tok => tok,
+        };
+
+        Some(Spanned {
+            value,
+            span: start..self.ci,
+            line,
+            column,
+        })
+    }
+
+    /// Turns the lexer into an iterator of tokens with their positions
+    pub fn spanned(mut self) -> impl Iterator<Item = Spanned<Token<'a>>> {
+        std::iter::from_fn(move || self.next_spanned())
+    }
+
     fn skip_nontokens(&mut self) {
-        while self.c == ' ' || self.c == '\t' || self.c == '\r' || self.c == '\n' {
+        while !self.is_at_end() && self.c.is_whitespace() {
             self.scan_char()
         }
     }
@@ -51,25 +202,72 @@ impl<'a> Lexer<'a> {
             return Token::EOF;
         }
 
+        if self.c.is_ascii_alphanumeric() || self.c == '#' {
+            if let Some(tok) = self.scan_recognized() {
+                return tok;
+            }
+        }
+
         // If digit, return token with number
         if self.c.is_numeric() {
             return self.scan_number();
         }
 
-        if self.c.is_alphabetic() {
-            return self.scan_word();
+        if is_cjk(self.c) {
+            return self.scan_cjk();
         }
 
-        if self.is_symbol(self.c) {
-            return self.scan_symbol();
+        // Identifiers like `_private` and `__init__` are words too
+        if self.c.is_alphabetic() || (self.c == '_' && self.peek().is_some_and(is_word_char)) {
+            return self.scan_word();
         }
 
-        Token::Error
+        // Anything else, like punctuation, emoji or a stray accent, is a
+        // symbol, so one odd character can't end the text early
+        self.scan_symbol()
+    }
+
+    /// A structured token starting at the current character, if one of the
+    /// enabled kinds is recognised there
+    fn scan_recognized(&mut self) -> Option<Token<'a>> {
+        type Recognizer<'a> = (bool, fn(&str) -> Option<usize>, fn(&'a str) -> Token<'a>);
+
+        let on = self.recognizers;
+        // `C#` is a word and a symbol, not the start of a hashtag
+        let after_word = self.input[..self.ci]
+            .chars()
+            .next_back()
+            .is_some_and(is_word_char);
+        let recognizers: [Recognizer<'a>; 7] = [
+            (on.urls, url_len, Token::Url),
+            (on.emails, email_len, Token::Email),
+            (on.hashtags && !after_word, hashtag_len, Token::Hashtag),
+            (on.hex, hex_len, Token::Hex),
+            (on.dates, date_len, Token::Date),
+            (on.versions, version_len, Token::Version),
+            (on.decimals, decimal_len, Token::Decimal),
+        ];
+
+        let rest = &self.input[self.ci..];
+        let (len, token) = recognizers
+            .into_iter()
+            .filter(|(enabled, _, _)| *enabled)
+            .find_map(|(_, len, token)| Some((len(rest)?, token)))?;
+
+        let startpos = self.ci;
+        while self.ci < startpos + len {
+            self.scan_char();
+        }
+        Some(token(&self.input[startpos..self.ci]))
     }
 
     fn scan_word(&mut self) -> Token<'a> {
         let startpos = self.ci;
-        while self.c.is_alphanumeric() || self.c == '\'' || self.c == '-' {
+        // Apostrophes and hyphens only count inside a word, as in `they're`
+        // or `they’re`
+        while is_word_char(self.c)
+            || (matches!(self.c, '\'' | '\u{2019}' | '-') && self.peek().is_some_and(is_word_char))
+        {
             self.scan_char();
         }
 
@@ -87,23 +285,31 @@ impl<'a> Lexer<'a> {
 
     fn scan_symbol(&mut self) -> Token<'a> {
         let startpos = self.ci;
-        while self.is_symbol(self.c) {
+        self.scan_char();
+        while !self.is_at_end() && is_symbol(self.c) {
             self.scan_char();
         }
 
         Token::Symbol(&self.input[startpos..self.ci])
     }
 
-    fn is_symbol(&self, c: char) -> bool {
-        match c {
-            '!' | '@' | '#' | '$' | '%' | '^' | '&' | '*' | '(' | ')' | '-' | '_' | '+' | '='
-            | '{' | '}' | '[' | ']' | ':' | ';' | '"' | '\'' | '<' | '>' | ',' | '.' | '?'
-            | '/' | '\\' | '|' => true,
-            _ => false,
+    fn scan_cjk(&mut self) -> Token<'a> {
+        let startpos = self.ci;
+        while is_cjk(self.c) || (self.ci > startpos && is_combining_mark(self.c)) {
+            self.scan_char();
         }
+
+        Token::Cjk(&self.input[startpos..self.ci])
     }
 
     fn scan_char(&mut self) {
+        if self.c == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+
         // If there is a next character, use it
         if let Some((ci, c)) = self.iter.next() {
             self.ci = ci;
@@ -115,11 +321,207 @@ impl<'a> Lexer<'a> {
         }
     }
 
+    fn peek(&self) -> Option<char> {
+        self.iter.clone().next().map(|(_, c)| c)
+    }
+
     fn is_at_end(&self) -> bool {
         self.ci >= self.input.len()
     }
 }
 
+/// Letters, digits and underscores, so `snake_case` stays one word, and
+/// combining accents, which belong to the letter before them
+fn is_word_char(c: char) -> bool {
+    (c.is_alphanumeric() || c == '_' || is_combining_mark(c)) && !is_cjk(c)
+}
+
+fn is_symbol(c: char) -> bool {
+    !(c.is_whitespace() || c.is_alphanumeric() || is_cjk(c))
+}
+
+/// Han ideographs, Japanese kana and Hangul syllables
+fn is_cjk(c: char) -> bool {
+    matches!(c,
+        '\u{3040}'..='\u{30ff}' // Hiragana and Katakana
+        | '\u{31f0}'..='\u{31ff}' // Katakana extensions
+        | '\u{3400}'..='\u{4dbf}' // CJK Extension A
+        | '\u{4e00}'..='\u{9fff}' // CJK Unified Ideographs
+        | '\u{ac00}'..='\u{d7af}' // Hangul syllables
+        | '\u{f900}'..='\u{faff}' // CJK Compatibility Ideographs
+        | '\u{ff66}'..='\u{ff9f}' // Halfwidth Katakana
+        | '\u{20000}'..='\u{3134f}' // CJK Extensions B to G
+    )
+}
+
+// Recognisers run at the start of every word, so the runs they look through
+// before they can tell whether a token matches are capped, keeping the lexer
+// linear on long dotted text like `a.b.c…` that never turns into one
+const MAX_SCHEME_LEN: usize = 32;
+const MAX_LOCAL_PART_LEN: usize = 64;
+const MAX_DOMAIN_LEN: usize = 255;
+const MAX_NUMBER_LEN: usize = 64;
+
+/// The length of the run of bytes at the start of `s` matching `pred`, or
+/// `None` if it is longer than `max`
+fn run_len(s: &str, max: usize, pred: impl Fn(u8) -> bool) -> Option<usize> {
+    let len = s.bytes().take(max + 1).take_while(|&b| pred(b)).count();
+    (len <= max).then_some(len)
+}
+
+/// The length of a URL with a scheme at the start of `s`. Punctuation at
+/// the end is left out, as it most likely ends the sentence instead.
+fn url_len(s: &str) -> Option<usize> {
+    if !s.starts_with(|c: char| c.is_ascii_alphabetic()) {
+        return None;
+    }
+    let scheme = run_len(s, MAX_SCHEME_LEN, |b| {
+        b.is_ascii_alphanumeric() || b"+.-".contains(&b)
+    })?;
+    if !s[scheme..].starts_with("://") {
+        return None;
+    }
+
+    let end = s
+        .find(|c: char| c.is_whitespace() || "<>\"`".contains(c))
+        .unwrap_or(s.len());
+    let mut url = &s[..end];
+    let open = url.matches('(').count();
+    let mut close = url.matches(')').count();
+    loop {
+        let trimmed = url.trim_end_matches(['.', ',', ';', ':', '!', '?', '\'']);
+        // Keep closing parentheses that have an opening one, as in Wikipedia links
+        let trimmed = match trimmed.strip_suffix(')') {
+            Some(inner) if open < close => {
+                close -= 1;
+                inner
+            }
+            _ => trimmed,
+        };
+        if trimmed.len() == url.len() {
+            break;
+        }
+        url = trimmed;
+    }
+
+    (url.len() > scheme + 3).then_some(url.len())
+}
+
+/// The length of an email address at the start of `s`
+fn email_len(s: &str) -> Option<usize> {
+    let local = run_len(s, MAX_LOCAL_PART_LEN, |b| {
+        b.is_ascii_alphanumeric() || b"._%+-".contains(&b)
+    })?;
+    if local == 0 || s.as_bytes().get(local) != Some(&b'@') {
+        return None;
+    }
+
+    let rest = &s[local + 1..];
+    let len = run_len(rest, MAX_DOMAIN_LEN, |b| {
+        b.is_ascii_alphanumeric() || b".-".contains(&b)
+    })?;
+    let domain = rest[..len].trim_end_matches(['.', '-']);
+
+    let mut labels = domain.split('.');
+    let tld = labels.next_back()?;
+    let valid = domain.contains('.')
+        && labels.all(|label| !label.is_empty())
+        && tld.len() >= 2
+        && tld.bytes().all(|b| b.is_ascii_alphabetic());
+    valid.then_some(local + 1 + domain.len())
+}
+
+fn hashtag_len(s: &str) -> Option<usize> {
+    let tag = s.strip_prefix('#')?;
+    if !tag.starts_with(char::is_alphabetic) {
+        return None;
+    }
+    let len: usize = tag
+        .chars()
+        .take_while(|&c| is_word_char(c))
+        .map(char::len_utf8)
+        .sum();
+    Some(1 + len)
+}
+
+fn hex_len(s: &str) -> Option<usize> {
+    let digits = s.strip_prefix("0x").or_else(|| s.strip_prefix("0X"))?;
+    let len = digits.bytes().take_while(u8::is_ascii_hexdigit).count();
+    (len > 0 && !continues_word(&digits[len..])).then_some(2 + len)
+}
+
+/// The length of a `YYYY-MM-DD` date at the start of `s`
+fn date_len(s: &str) -> Option<usize> {
+    let date = s.get(..10)?.as_bytes();
+    let number = |range: Range<usize>| -> Option<u32> {
+        let digits = &date[range];
+        digits
+            .iter()
+            .all(u8::is_ascii_digit)
+            .then(|| digits.iter().fold(0, |n, d| n * 10 + u32::from(d - b'0')))
+    };
+
+    let valid = date[4] == b'-'
+        && date[7] == b'-'
+        && number(0..4).is_some()
+        && number(5..7).is_some_and(|month| (1..=12).contains(&month))
+        && number(8..10).is_some_and(|day| (1..=31).contains(&day));
+    (valid && !continues_word(&s[10..])).then_some(10)
+}
+
+/// The length of a version at the start of `s`: dotted numbers with a `v`
+/// in front, or three or more of them without
+fn version_len(s: &str) -> Option<usize> {
+    let numbers = s.strip_prefix(['v', 'V']).unwrap_or(s);
+    let prefix = s.len() - numbers.len();
+
+    let mut len = digits_len(numbers);
+    let mut parts = 1;
+    while len > 0 && len <= MAX_NUMBER_LEN && numbers[len..].starts_with('.') {
+        match digits_len(&numbers[len + 1..]) {
+            0 => break,
+            digits => len += 1 + digits,
+        }
+        parts += 1;
+    }
+
+    let enough = if prefix > 0 { parts >= 2 } else { parts >= 3 };
+    (enough && len <= MAX_NUMBER_LEN && !continues_word(&numbers[len..])).then_some(prefix + len)
+}
+
+/// The length of a number with thousands separators or a fraction at the
+/// start of `s`, like `1,000.5`. Separated groups must have three digits, so
+/// lists like `1,2,3` stay separate numbers.
+fn decimal_len(s: &str) -> Option<usize> {
+    let mut len = digits_len(s);
+    if len == 0 {
+        return None;
+    }
+    let whole = len;
+
+    while len <= MAX_NUMBER_LEN && s[len..].starts_with(',') && digits_len(&s[len + 1..]) == 3 {
+        len += 4;
+    }
+    if s[len..].starts_with('.') {
+        len += match digits_len(&s[len + 1..]) {
+            0 => 0,
+            digits => 1 + digits,
+        };
+    }
+
+    (len > whole && len <= MAX_NUMBER_LEN && !continues_word(&s[len..])).then_some(len)
+}
+
+fn digits_len(s: &str) -> usize {
+    s.bytes().take_while(u8::is_ascii_digit).count()
+}
+
+/// Whether `rest` carries on the token before it, so it wasn't recognised
+/// after all, like the `g` of `0x1Fg`
+fn continues_word(rest: &str) -> bool {
+    rest.starts_with(is_word_char)
+}
+
 impl<'a> Iterator for Lexer<'a> {
     type Item = Token<'a>;
 
@@ -144,7 +546,7 @@ mod tests {
         let leading_spaces = "   \t\t   \n\r  test";
         let target = "test";
 
-        let lexer = Lexer::new(&leading_spaces);
+        let lexer = Lexer::new(leading_spaces);
         let toks: Vec<Token> = lexer.collect();
 
         assert_eq!(toks.len(), 1);
@@ -188,6 +590,18 @@ mod tests {
         assert_eq!(lexer.next_token(), Token::EOF);
     }
 
+    /// Should keep identifiers with underscores whole
+    #[test]
+    fn scan_identifiers() {
+        let mut lexer = Lexer::new("snake_case_name __init__ _ x_");
+
+        assert_eq!(lexer.next_token(), Token::Word("snake_case_name"));
+        assert_eq!(lexer.next_token(), Token::Word("__init__"));
+        assert_eq!(lexer.next_token(), Token::Symbol("_"));
+        assert_eq!(lexer.next_token(), Token::Word("x_"));
+        assert_eq!(lexer.next_token(), Token::EOF);
+    }
+
     /// Should scan 'symbols' from text
     #[test]
     fn scan_symbol() {
@@ -216,4 +630,162 @@ mod tests {
         assert_eq!(lexer.next_token(), Token::Word("please"));
         assert_eq!(lexer.next_token(), Token::EOF);
     }
+
+    /// Should give each token's byte range, line and column
+    #[test]
+    fn spans() {
+        let text = "fn main() {\n    println!(\"héllo\");\n}";
+
+        let toks: Vec<_> = Lexer::new(text)
+            .spanned()
+            .map(|tok| (tok.value, tok.span, tok.line, tok.column))
+            .collect();
+
+        assert_eq!(
+            toks,
+            vec![
+                (Token::Word("fn"), 0..2, 1, 1),
+                (Token::Word("main"), 3..7, 1, 4),
+                (Token::Symbol("()"), 7..9, 1, 8),
+                (Token::Symbol("{"), 10..11, 1, 11),
+                (Token::Word("println"), 16..23, 2, 5),
+                (Token::Symbol("!(\""), 23..26, 2, 12),
+                (Token::Word("héllo"), 26..32, 2, 15),
+                (Token::Symbol("\");"), 32..35, 2, 20),
+                (Token::Symbol("}"), 36..37, 3, 1),
+            ]
+        );
+    }
+
+    /// Unknown characters should be skipped rather than end the text
+    #[test]
+    fn unknown_characters() {
+        let text = "caf\u{65}\u{301} 🦀\u{200d}🔥 non\u{a0}breaking — «done»";
+
+        let words: Vec<_> = Lexer::new(text)
+            .filter(|tok| matches!(tok, Token::Word(_)))
+            .collect();
+
+        assert_eq!(
+            words,
+            vec![
+                Token::Word("caf\u{65}\u{301}"),
+                Token::Word("non"),
+                Token::Word("breaking"),
+                Token::Word("done"),
+            ]
+        );
+    }
+
+    /// Runs of CJK characters should be kept apart from the words around them
+    #[test]
+    fn scan_cjk() {
+        let mut lexer = Lexer::new("Rust是一种语言。東京タワー 2024年");
+
+        assert_eq!(lexer.next_token(), Token::Word("Rust"));
+        assert_eq!(lexer.next_token(), Token::Cjk("是一种语言"));
+        assert_eq!(lexer.next_token(), Token::Symbol("。"));
+        assert_eq!(lexer.next_token(), Token::Cjk("東京タワー"));
+        assert_eq!(lexer.next_token(), Token::Number("2024"));
+        assert_eq!(lexer.next_token(), Token::Cjk("年"));
+        assert_eq!(lexer.next_token(), Token::EOF);
+    }
+
+    /// Should keep apostrophes and hyphens inside words but not at their ends
+    #[test]
+    fn word_punctuation() {
+        let words: Vec<_> = Lexer::new("well-known rock- 'quoted' dogs' don’t ‘curly’")
+            .filter(|tok| matches!(tok, Token::Word(_)))
+            .collect();
+
+        assert_eq!(
+            words,
+            vec![
+                Token::Word("well-known"),
+                Token::Word("rock"),
+                Token::Word("quoted"),
+                Token::Word("dogs"),
+                Token::Word("don’t"),
+                Token::Word("curly"),
+            ]
+        );
+    }
+
+    /// Should recognise structured tokens whole
+    #[test]
+    fn scan_structured() {
+        let text = "3.14 1,000.50 1,2 v1.2 1.2.3 192.168.0.1 0x1F 2024-06-01 \
+                    ann.lee+x@mail.example.org, https://en.wikipedia.org/wiki/Rust_(language). #rustlang";
+
+        let toks: Vec<_> = Lexer::new(text)
+            .filter(|tok| !matches!(tok, Token::Symbol(_)))
+            .collect();
+
+        assert_eq!(
+            toks,
+            vec![
+                Token::Decimal("3.14"),
+                Token::Decimal("1,000.50"),
+                Token::Number("1"),
+                Token::Number("2"),
+                Token::Version("v1.2"),
+                Token::Version("1.2.3"),
+                Token::Version("192.168.0.1"),
+                Token::Hex("0x1F"),
+                Token::Date("2024-06-01"),
+                Token::Email("ann.lee+x@mail.example.org"),
+                Token::Url("https://en.wikipedia.org/wiki/Rust_(language)"),
+                Token::Hashtag("#rustlang"),
+            ]
+        );
+    }
+
+    /// Near misses should fall back to plain tokens
+    #[test]
+    fn scan_not_structured() {
+        let text = "0x 0x1Fg 2024-13-01 v1 a@b C#sharp ftp://";
+
+        let toks: Vec<_> = Lexer::new(text).collect();
+
+        assert!(
+            toks.iter()
+                .all(|tok| matches!(tok, Token::Word(_) | Token::Number(_) | Token::Symbol(_))),
+            "{:?}",
+            toks
+        );
+    }
+
+    /// Long runs that only look like the start of a structured token should
+    /// be lexed in linear time, not rescanned from every word in them
+    #[test]
+    fn long_dotted_runs() {
+        for unit in ["ab.", "1.", "1,000", "x.y-", "a.b@"] {
+            let text = unit.repeat(50_000);
+
+            let last = Lexer::new(&text).spanned().last().unwrap();
+
+            assert_eq!(last.span.end, text.len(), "{}", unit);
+        }
+    }
+
+    /// Should only recognise the kinds asked for
+    #[test]
+    fn recognizers() {
+        let only_dates = Recognizers {
+            dates: true,
+            ..Recognizers::NONE
+        };
+
+        let toks: Vec<_> = Lexer::with_recognizers("2024-06-01 3.14", only_dates).collect();
+
+        assert_eq!(
+            toks,
+            vec![
+                Token::Date("2024-06-01"),
+                Token::Number("3"),
+                Token::Symbol("."),
+                Token::Number("14"),
+            ]
+        );
+    }
 }
@@ -1,13 +1,33 @@
 // Argument handling
 pub mod arguments;
 
+// Walk directories for files to index
+pub mod crawl;
+
 // Extract content from the files
 pub mod extract;
 
+// Where documents came from, files or archive members
+pub mod doc_path;
+
+// Term to document postings
+pub mod index;
 // The model we query
 pub mod model;
+// Scoring functions used to rank query results
+pub mod ranker;
 // The lexer to tokenize raw text
 pub mod lexer;
+// Turns text into index terms
+pub mod analysis;
+// Built-in and user supplied stop word lists
+pub mod stop_words;
+// Guessing the language of a document
+pub mod language;
+// Query parsing and matching
+pub mod query;
+// Passages of matching documents, with the matches highlighted
+pub mod snippet;
 
 // All stemming related logic
 pub mod snowball;
@@ -1,26 +1,76 @@
 use std::error::Error;
-use std::path::PathBuf;
+use std::io::{self, IsTerminal};
+use std::path::Path;
 use std::process::ExitCode;
 
 use clap::Parser;
+use globset::Glob;
 
 use rust_search::arguments::{Arguments, Commands};
-use rust_search::model::Model;
+use rust_search::crawl::{self, Filter};
+use rust_search::doc_path::DocPath;
+use rust_search::extract::RecordFields;
+use rust_search::model::{Model, SearchResult};
+use rust_search::query;
+use rust_search::ranker::Ranker;
 
 fn run() -> Result<(), Box<dyn Error>> {
     let args = Arguments::parse();
 
+    if let Commands::Init { analysis } = &args.command {
+        if args.path.exists() {
+            return Err(format!("A model already exists at {}", args.path.display()).into());
+        }
+
+        return Ok(Model::new(analysis.analyzer()?).save(&args.path)?);
+    }
+
     // Build the model from user or default path
     let mut model = Model::from(&args.path)?;
 
     match &args.command {
-        Commands::Add { path } => model.add(path)?,
-        Commands::Search { query, interactive } => {
+        Commands::Init { .. } => unreachable!("handled above"),
+        Commands::Add {
+            path,
+            include,
+            exclude,
+            stemmer,
+            records,
+        } => {
+            if let Some(stemmer) = stemmer {
+                model.expect_stemmer(stemmer.algorithm())?;
+            }
+
+            let filter = Filter::new(include, exclude)?;
+            run_add(&mut model, path, &filter, records.fields().as_ref());
+            model.save(&args.path)?;
+        }
+        Commands::Remove { path } => {
+            run_remove(&mut model, path)?;
+            model.save(&args.path)?;
+        }
+        Commands::Sync => {
+            run_sync(&mut model);
+            model.save(&args.path)?;
+        }
+        Commands::Search {
+            query,
+            interactive,
+            ranking,
+            stemmer,
+            passages,
+            limit,
+        } => {
+            if let Some(stemmer) = stemmer {
+                model.expect_stemmer(stemmer.algorithm())?;
+            }
+
+            let ranker = ranking.ranker();
             if *interactive {
-                run_interactive_search(&model)?;
+                run_interactive_search(&model, ranker.as_ref(), *passages, *limit)?;
             } else if let Some(q) = query {
-                let results = model.query(q);
-                format_result(&results);
+                let results = model.query(q, ranker.as_ref())?;
+                format_result(&model, q, &results, *passages, *limit);
             } else {
                 eprintln!("No query provided.");
             }
@@ -30,7 +80,108 @@ fn run() -> Result<(), Box<dyn Error>> {
     Ok(())
 }
 
-fn run_interactive_search(model: &Model) -> Result<(), Box<dyn Error>> {
+fn run_add(model: &mut Model, root: &Path, filter: &Filter, records: Option<&RecordFields>) {
+    let (mut added, mut skipped, mut failed) = (0, 0, 0);
+
+    for entry in crawl::walk(root, filter) {
+        let path = match entry {
+            Ok(path) => path,
+            Err(e) => {
+                eprintln!("Failed: {}", e);
+                failed += 1;
+                continue;
+            }
+        };
+
+        let result = match records {
+            Some(fields) => model.add_records(&path, fields).map(Some),
+            None => model.add(&path).map(|()| None),
+        };
+
+        match result {
+            Ok(Some(count)) => {
+                println!("Added: {} ({} records)", path.display(), count);
+                added += 1;
+            }
+            Ok(None) => {
+                println!("Added: {}", path.display());
+                added += 1;
+            }
+            // Binary files and unsupported formats are expected when walking a directory
+            Err(e) if e.kind() == io::ErrorKind::InvalidInput => {
+                println!("Skipped: {} ({})", path.display(), e);
+                skipped += 1;
+            }
+            Err(e) => {
+                eprintln!("Failed: {} ({})", path.display(), e);
+                failed += 1;
+            }
+        }
+    }
+
+    println!("{} added, {} skipped, {} failed", added, skipped, failed);
+}
+
+fn run_remove(model: &mut Model, pattern: &str) -> Result<(), Box<dyn Error>> {
+    // Paths are kept normalised in the model, so `./docs/a.txt` is `docs/a.txt`
+    let exact = DocPath::parse(pattern);
+    let in_file = model.normalize_path(exact.fs_path())?;
+    let exact = exact.with_fs_path(in_file);
+    let file = model.normalize_path(Path::new(pattern))?;
+    let matcher = Glob::new(pattern)?.compile_matcher();
+
+    // A file's path matches every document in it, like an archive's members or a file's records
+    let mut matched: Vec<DocPath> = model
+        .paths()
+        .filter(|path| {
+            *path == &exact
+                || path.fs_path() == file
+                || matcher.is_match(path.fs_path())
+                || matcher.is_match(path.to_string())
+        })
+        .cloned()
+        .collect();
+    matched.sort();
+
+    if matched.is_empty() {
+        println!("No documents matched {}", pattern);
+    }
+
+    for path in &matched {
+        model.remove(path);
+        println!("Removed: {}", path);
+    }
+
+    Ok(())
+}
+
+fn run_sync(model: &mut Model) {
+    let report = model.sync();
+
+    for path in &report.updated {
+        println!("Updated: {}", path.display());
+    }
+    for path in &report.removed {
+        println!("Removed: {}", path);
+    }
+    for (path, e) in &report.failed {
+        eprintln!("Failed: {} ({})", path.display(), e);
+    }
+
+    println!(
+        "{} updated, {} removed, {} failed",
+        report.updated.len(),
+        report.removed.len(),
+        report.failed.len()
+    );
+}
+
+fn run_interactive_search(
+    model: &Model,
+    ranker: &dyn Ranker,
+    passages: usize,
+    limit: usize,
+) -> Result<(), Box<dyn Error>> {
     use std::io::{self, Write};
 
     loop {
@@ -45,21 +196,77 @@ fn run_interactive_search(model: &Model) -> Result<(), Box<dyn Error>> {
         }
 
         if !input.is_empty() {
-            let results = model.query(&input);
-            format_result(&results);
+            match model.query(&input, ranker) {
+                Ok(results) => format_result(model, &input, &results, passages, limit),
+                Err(e) => eprintln!("Invalid query: {}", e),
+            }
         }
     }
 
     Ok(())
 }
 
-fn format_result(results: &Vec<(f32, &PathBuf)>) {
-    if results.len() == 0 {
-        println!("No documents matched your query.")
+/// Prints the first `limit` results with up to `passages` of their best
+/// matching passages, highlighting matches in bold on a terminal and between
+/// `[[` and `]]` when the output goes to another program
+fn format_result(
+    model: &Model,
+    query: &str,
+    results: &[SearchResult],
+    passages: usize,
+    limit: usize,
+) {
+    if results.is_empty() {
+        println!("No documents matched your query.");
+        return;
+    }
+
+    // The query already parsed once to find the results
+    let query = query::parse(query, model.analyzer()).ok();
+    let (open, close) = if io::stdout().is_terminal() {
+        ("\x1b[1;33m", "\x1b[0m")
     } else {
-        for (index, (score, path)) in results.iter().enumerate() {
-            println!("{}. {:.2} - {}", index + 1, score, path.display());
+        ("[[", "]]")
+    };
+
+    let shown = &results[..results.len().min(limit)];
+    // Only the results shown need passages, and files behind several of them are read once
+    let snippets = match &query {
+        Some(query) => {
+            let paths: Vec<_> = shown.iter().map(|result| result.path).collect();
+            model.snippets_for(&paths, query, passages)
         }
+        None => shown.iter().map(|_| Ok(Vec::new())).collect(),
+    };
+
+    for ((index, result), snippets) in shown.iter().enumerate().zip(snippets) {
+        let page = match result.page {
+            Some(page) => format!(" (page {})", page),
+            None => String::new(),
+        };
+        println!(
+            "{}. {:.2} - {}{}",
+            index + 1,
+            result.score,
+            result.path,
+            page
+        );
+
+        match snippets {
+            Ok(snippets) => {
+                for snippet in snippets {
+                    println!("   {}", snippet.render(open, close));
+                }
+            }
+            Err(e) => eprintln!("   No passage from {}: {}", result.path, e),
+        }
+    }
+
+    if results.len() > shown.len() {
+        println!(
+            "{} more results, shown with a higher --limit",
+            results.len() - shown.len()
+        );
     }
 }
 
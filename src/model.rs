@@ -1,178 +1,1411 @@
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::env;
 use std::error::Error;
+use std::fmt;
 use std::fs::{self, File};
 use std::io::{self, BufWriter};
-use std::path::PathBuf;
+use std::path::{Component, Path, PathBuf};
 use std::time::SystemTime;
 
-use crate::lexer::Lexer;
+use crate::analysis::Analyzer;
 
-use crate::extract::Extractor;
+use crate::doc_path::DocPath;
+
+use crate::extract::{self, Content, RecordFields, Registry};
+
+use crate::index::{DocId, FieldRange, InvertedIndex};
+
+use crate::language::{self, Language};
 
-use crate::snowball::stem::stem;
 use crate::snowball::StemmingAlgorithm;
 
-// TF: The frequency of each term in an individual document
-// DF: The number of occurrences of a term in the entire document set
+use crate::ranker::{CorpusStats, Ranker, BODY};
+
+use crate::query::{self, Hit, Query, QueryError, Searchable, Searcher};
 
-type Documents = HashMap<PathBuf, Document>;
-type TermFrequency = HashMap<String, usize>;
-type DocumentFrequency = HashMap<String, usize>;
+use crate::snippet::{self, Snippet};
+
+// TF: The frequency of each term in an individual document
+// DF: The number of documents in the entire document set containing a term
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Document {
-    /// Map of every term in the document and its respective frequency
-    tf: TermFrequency,
+    /// Where the document was read from
+    #[serde(flatten)]
+    path: DocPath,
     /// The total number of tokens in document
     count: usize,
-    /// Time this document was indexed
+    /// Where each field of the document was indexed. Models saved before
+    /// documents had fields are loaded as a single body field.
+    #[serde(default)]
+    fields: Vec<FieldRange>,
+    /// The position each page starts at, for paged formats like PDF
+    #[serde(default)]
+    pages: Vec<u32>,
+    /// Modification time of the file when it was indexed, which is the
+    /// archive's for archive members
     last_modified: SystemTime,
+    /// Hash of the raw file content when it was indexed, which is the
+    /// whole archive's for archive members
+    #[serde(default)]
+    hash: u64,
+    /// The detected language, if detection is enabled and could tell
+    #[serde(default)]
+    language: Option<Language>,
+}
+
+/// A document matching a query
+#[derive(Debug, PartialEq)]
+pub struct SearchResult<'a> {
+    pub score: f32,
+    pub path: &'a DocPath,
+    /// The page of the first match in the body, for paged formats like PDF
+    pub page: Option<usize>,
+}
+
+/// An operation asked for a different stemmer than the model was built with
+#[derive(Debug)]
+pub struct StemmerMismatch {
+    pub model: Option<StemmingAlgorithm>,
+    pub requested: Option<StemmingAlgorithm>,
+}
+
+impl fmt::Display for StemmerMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = |stemmer: Option<StemmingAlgorithm>| match stemmer {
+            Some(alg) => alg.to_string(),
+            None => "none".to_string(),
+        };
+
+        write!(
+            f,
+            "the model was indexed with the {} stemmer, but {} was requested",
+            name(self.model),
+            name(self.requested)
+        )
+    }
+}
+
+impl Error for StemmerMismatch {}
+
+/// A model saved before the inverted index, which kept each document's term
+/// counts by path. Only the paths are still of use.
+#[derive(Deserialize)]
+struct LegacyModel {
+    documents: HashMap<PathBuf, serde::de::IgnoredAny>,
+}
+
+/// What changed in the model during a `Model::sync`
+#[derive(Debug, Default)]
+pub struct SyncReport {
+    /// Files whose content changed and were re-extracted
+    pub updated: Vec<PathBuf>,
+    /// Documents whose file no longer exists
+    pub removed: Vec<DocPath>,
+    /// Files that could not be re-extracted, and why
+    pub failed: Vec<(PathBuf, io::Error)>,
 }
 
 #[derive(Default, Debug, Serialize, Deserialize)]
 pub struct Model {
-    /// A map of paths to respective Document struct
-    documents: Documents,
+    /// Every indexed document by id
+    documents: HashMap<DocId, Document>,
+
+    /// Map of each term in the corpus to the documents containing it
+    index: InvertedIndex,
+
+    /// The id given to the next new document
+    next_id: DocId,
+
+    /// How text is turned into terms, fixed when the model is created
+    #[serde(default)]
+    analyzer: Analyzer,
+
+    /// Files indexed as one document per record, and which fields of the
+    /// records to use, so re-indexing them does the same
+    #[serde(default)]
+    records: HashMap<PathBuf, RecordFields>,
+
+    /// Lookup from a document's path to its id, rebuilt on load
+    #[serde(skip)]
+    ids: HashMap<DocPath, DocId>,
+
+    /// The documents read from each file on disk, rebuilt on load
+    #[serde(skip)]
+    files: HashMap<PathBuf, HashSet<DocId>>,
+
+    /// The directory of the model's file, which relative paths in the model
+    /// are relative to. Empty for the current directory.
+    #[serde(skip)]
+    root: PathBuf,
 
-    /// Map of each term in the corpus and its respective frequency
-    df: DocumentFrequency,
+    /// Sum of the token counts of every document, rebuilt on load
+    #[serde(skip)]
+    total_len: usize,
+
+    /// Sum of the token counts of each field across documents, rebuilt on load
+    #[serde(skip)]
+    field_len: HashMap<String, usize>,
+
+    /// How each format is extracted, which isn't saved with the model
+    #[serde(skip)]
+    extractors: Registry,
 }
 
 impl Model {
+    /// Creates an empty model that analyses text with `analyzer`
+    pub fn new(analyzer: Analyzer) -> Self {
+        Self {
+            analyzer,
+            ..Default::default()
+        }
+    }
+
     pub fn from(path: &PathBuf) -> Result<Model, Box<dyn Error>> {
+        let root = match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => clean(&std::path::absolute(dir)?),
+            _ => PathBuf::new(),
+        };
+
         if path.exists() {
             println!("Using model at: {:?}", path);
             let content = fs::read_to_string(path)?;
-            let model: Model = serde_json::from_str(&content)?;
+            let mut model: Model = match serde_json::from_str(&content) {
+                Ok(model) => model,
+                Err(e) => match serde_json::from_str::<LegacyModel>(&content) {
+                    Ok(legacy) => return Ok(Model::from_legacy(legacy, root)),
+                    Err(_) => return Err(e.into()),
+                },
+            };
+            model.root = root;
+
+            for (id, doc) in model.documents.iter_mut() {
+                if doc.fields.is_empty() {
+                    doc.fields.push(FieldRange {
+                        name: BODY.to_string(),
+                        start: 0,
+                        len: doc.count as u32,
+                    });
+                }
+
+                model.ids.insert(doc.path.clone(), *id);
+                let file = doc.path.fs_path().to_path_buf();
+                model.files.entry(file).or_default().insert(*id);
+                model.total_len += doc.count;
+                for field in &doc.fields {
+                    *model.field_len.entry(field.name.clone()).or_default() += field.len as usize;
+                }
+            }
+
             Ok(model)
         } else {
-            Ok(Default::default())
+            Ok(Model {
+                root,
+                ..Default::default()
+            })
+        }
+    }
+
+    /// Indexes the files of a model saved before the inverted index again,
+    /// since it kept no positions. Files that can't be read are dropped.
+    fn from_legacy(legacy: LegacyModel, root: PathBuf) -> Model {
+        println!("Re-indexing a model from an older version");
+        let mut paths: Vec<_> = legacy.documents.into_keys().collect();
+        paths.sort();
+
+        let mut model = Model {
+            root,
+            ..Default::default()
+        };
+        for path in paths {
+            if let Err(e) = model.add(&path) {
+                eprintln!("Dropping {:?}: {}", path, e);
+            }
+        }
+        model
+    }
+
+    /// How a path given by the user is kept in the model: without `.` or
+    /// `..` components, and relative to the model's directory if it is in it,
+    /// so the same file is one document however it was named
+    pub fn normalize_path(&self, path: &Path) -> io::Result<PathBuf> {
+        let path = clean(&std::path::absolute(path)?);
+        let root = if self.root.as_os_str().is_empty() {
+            env::current_dir()?
+        } else {
+            self.root.clone()
+        };
+
+        Ok(path
+            .strip_prefix(&root)
+            .map_or_else(|_| path.clone(), Path::to_path_buf))
+    }
+
+    /// Where a path kept in the model is on disk
+    fn on_disk(&self, path: &Path) -> PathBuf {
+        self.root.join(path)
+    }
+
+    /// How the model turns text into terms
+    pub fn analyzer(&self) -> &Analyzer {
+        &self.analyzer
+    }
+
+    /// The extractors documents are read with, where new formats can be
+    /// registered. They aren't saved, so register them again after loading.
+    pub fn extractors_mut(&mut self) -> &mut Registry {
+        &mut self.extractors
+    }
+
+    /// Fails unless the model was indexed with `stemmer`, since terms from
+    /// different stemmers never line up
+    pub fn expect_stemmer(
+        &self,
+        stemmer: Option<StemmingAlgorithm>,
+    ) -> Result<(), StemmerMismatch> {
+        if self.analyzer.stemmer == stemmer {
+            Ok(())
+        } else {
+            Err(StemmerMismatch {
+                model: self.analyzer.stemmer,
+                requested: stemmer,
+            })
         }
     }
 
-    // Saves the updated model
+    // Saves the updated model. The model is written to a temporary file next to
+    // `path` and renamed over it, so a crash mid-write leaves the old model intact
     pub fn save(&self, path: &PathBuf) -> io::Result<()> {
         println!("Saving model to {:?}", path);
 
-        let output = File::create(path)?;
-        let writer = BufWriter::new(output);
+        let mut tmp_name = path.file_name().unwrap_or_default().to_os_string();
+        tmp_name.push(".tmp");
+        let tmp_path = path.with_file_name(tmp_name);
 
-        // Use pretty writer for dev
-        serde_json::to_writer(writer, &self)?;
+        let result = self.write_to(&tmp_path);
+        if result.is_err() {
+            let _ = fs::remove_file(&tmp_path);
+        }
+        result?;
+
+        fs::rename(&tmp_path, path)?;
         println!("Saved!");
         Ok(())
     }
 
-    // Should probably just return document...
-    pub fn query(&self, query: &str) -> Vec<(f32, &PathBuf)> {
-        let mut matches = Vec::new();
+    fn write_to(&self, path: &PathBuf) -> io::Result<()> {
+        let output = File::create(path)?;
+        let mut writer = BufWriter::new(output);
+
+        serde_json::to_writer(&mut writer, &self)?;
+
+        // Make sure the data is on disk before it replaces the old model
+        writer.into_inner()?.sync_all()
+    }
+
+    /// Finds the documents matching a query, best first
+    pub fn query(
+        &self,
+        query: &str,
+        ranker: &dyn Ranker,
+    ) -> Result<Vec<SearchResult<'_>>, QueryError> {
+        let query = query::parse(query, &self.analyzer)?;
 
-        // Turn the query into stemmed tokens
-        let stemmed_tokens: Vec<_> = Lexer::new(query)
-            .filter_map(|tok| stem(tok, StemmingAlgorithm::Porter2).map(|s| s.to_string()))
+        let stats = self.stats();
+        let searcher = Searcher {
+            source: self,
+            ranker,
+            stats: &stats,
+        };
+
+        let mut matches: Vec<_> = query
+            .evaluate(&searcher)
+            .into_iter()
+            .map(|hit| SearchResult {
+                score: hit.score,
+                path: &self.documents[&hit.doc].path,
+                page: self.page(&hit),
+            })
             .collect();
 
-        for (path, doc) in &self.documents {
-            let rank = stemmed_tokens.iter().fold(0.0, |score, tok| {
-                score + compute_tf(&tok, doc) + compute_idf(self.documents.len(), &tok, &self.df)
-            });
+        matches
+            .sort_unstable_by(|a, b| b.score.total_cmp(&a.score).then_with(|| a.path.cmp(b.path)));
 
-            // Only push document that have some form of match
-            if rank > 0.0 {
-                matches.push((rank, path));
+        Ok(matches)
+    }
+
+    /// The page the first body match of a hit is on, for paged documents
+    fn page(&self, hit: &Hit) -> Option<usize> {
+        let doc = &self.documents[&hit.doc];
+        let body = doc.fields.iter().find(|f| f.name == BODY)?;
+        let span = hit.spans.iter().find(|s| body.contains(s.start))?;
+
+        match doc.pages.partition_point(|&start| start <= span.start) {
+            0 => None,
+            page => Some(page),
+        }
+    }
+
+    /// Up to `max` passages of a document that best match `query`, best
+    /// first, from its body or else from its other fields. Documents that
+    /// only matched on metadata or excluded terms get their opening
+    /// passage instead.
+    ///
+    /// The document is extracted again from its file, so the passages
+    /// follow any changes made since it was indexed.
+    pub fn snippets(&self, path: &DocPath, query: &Query, max: usize) -> io::Result<Vec<Snippet>> {
+        self.snippets_for(&[path], query, max).remove(0)
+    }
+
+    /// The passages of each of `paths`, like [`Model::snippets`], reading
+    /// each file once however many of its records or members there are
+    pub fn snippets_for(
+        &self,
+        paths: &[&DocPath],
+        query: &Query,
+        max: usize,
+    ) -> Vec<io::Result<Vec<Snippet>>> {
+        if max == 0 {
+            return paths.iter().map(|_| Ok(Vec::new())).collect();
+        }
+
+        let mut by_file: BTreeMap<&Path, BTreeSet<&DocPath>> = BTreeMap::new();
+        for path in paths {
+            by_file.entry(path.fs_path()).or_default().insert(path);
+        }
+        let mut contents: HashMap<&DocPath, io::Result<Content>> = HashMap::new();
+        for (file, docs) in by_file {
+            let docs: Vec<_> = docs.into_iter().collect();
+            contents.extend(docs.iter().copied().zip(self.contents(file, &docs)));
+        }
+
+        let terms = query.terms();
+        paths
+            .iter()
+            .map(|path| match &contents[path] {
+                Ok(content) => Ok(self.passages(path, content, &terms, max)),
+                Err(e) => Err(copy_error(e)),
+            })
+            .collect()
+    }
+
+    /// The passages of a document's extracted content
+    fn passages(
+        &self,
+        path: &DocPath,
+        content: &Content,
+        terms: &HashSet<String>,
+        max: usize,
+    ) -> Vec<Snippet> {
+        let language = self
+            .ids
+            .get(path)
+            .and_then(|id| self.documents[id].language);
+        let stemmer = language.map_or(self.analyzer.stemmer, |l| Some(l.stemmer()));
+
+        let body = content.body();
+        let others = content.fields().filter(|(name, _)| *name != BODY);
+        for (_, text) in std::iter::once((BODY, body)).chain(others) {
+            let found = snippet::snippets(text, terms, &self.analyzer, stemmer, max);
+            if !found.is_empty() {
+                return found;
             }
         }
 
-        matches.sort_unstable_by(|(a, _), (b, _)| b.partial_cmp(a).unwrap());
+        snippet::lead(body, &self.analyzer, stemmer)
+            .into_iter()
+            .collect()
+    }
 
-        matches
+    /// Extracts the content of documents from `file` again, splitting the
+    /// file into records or members at most once
+    fn contents(&self, file: &Path, docs: &[&DocPath]) -> Vec<io::Result<Content>> {
+        let bytes = match fs::read(self.on_disk(file)) {
+            Ok(bytes) => bytes,
+            Err(e) => return docs.iter().map(|_| Err(copy_error(&e))).collect(),
+        };
+        let not_found = |path: &DocPath| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("{} is no longer in {}", path, file.display()),
+            )
+        };
+
+        let mut records: Option<io::Result<HashMap<String, Content>>> = None;
+        let mut members: Option<io::Result<HashMap<DocPath, Vec<u8>>>> = None;
+        docs.iter()
+            .map(|&path| {
+                if let Some(id) = path.record_id() {
+                    let records = records.get_or_insert_with(|| {
+                        let fields = self.records.get(file).ok_or_else(|| not_found(path))?;
+                        let records = extract::records(file, &bytes, fields)?;
+                        Ok(records.into_iter().map(|r| (r.id, r.content)).collect())
+                    });
+                    return match records {
+                        Ok(records) => records.remove(id).ok_or_else(|| not_found(path)),
+                        Err(e) => Err(copy_error(e)),
+                    };
+                }
+
+                let Some(name) = path.member() else {
+                    return self.extractors.extract(file, &bytes);
+                };
+                let members = members.get_or_insert_with(|| {
+                    let members =
+                        extract::archive_members(&bytes)?.ok_or_else(|| not_found(path))?;
+                    Ok(members
+                        .into_iter()
+                        .map(|(member, bytes)| (DocPath::in_archive(file, &member), bytes))
+                        .collect())
+                });
+                match members {
+                    Ok(members) => {
+                        let member = members.remove(path).ok_or_else(|| not_found(path))?;
+                        self.extractors.extract(Path::new(name), &member)
+                    }
+                    Err(e) => Err(copy_error(e)),
+                }
+            })
+            .collect()
     }
 
-    // Gets a model path and a list of doc paths, parses and add
-    pub fn add(&mut self, path: &PathBuf) -> io::Result<()> {
-        let content = Extractor::extract(path)?;
+    fn stats(&self) -> CorpusStats {
+        let documents = self.documents.len();
+        let avg_len = if documents == 0 {
+            0.0
+        } else {
+            self.total_len as f32 / documents as f32
+        };
+
+        let avg_field_len = self
+            .field_len
+            .iter()
+            .map(|(field, &len)| (field.clone(), len as f32 / documents as f32))
+            .collect();
+
+        CorpusStats {
+            documents,
+            avg_len,
+            avg_field_len,
+        }
+    }
+
+    /// The number of documents containing a term
+    pub fn df(&self, term: &str) -> usize {
+        self.index.df(term)
+    }
+
+    /// Indexes a file, replacing any previous version of it.
+    ///
+    /// Zip archives and gzipped tarballs are opened and each member is
+    /// indexed as its own document, under a path like
+    /// `bundle.zip!/docs/intro.md`, unless an extractor is registered for
+    /// the archive's format. Members that can't be extracted are skipped, and
+    /// an archive with none that can is rejected with `InvalidInput`.
+    /// Files that were added with [`Model::add_records`] are split into
+    /// records again.
+    pub fn add(&mut self, path: &Path) -> io::Result<()> {
+        let path = self.normalize_path(path)?;
+        self.add_file(&path)
+    }
 
-        let lex = Lexer::new(&content);
+    /// Indexes a file by the path it is kept under in the model
+    fn add_file(&mut self, path: &Path) -> io::Result<()> {
+        let bytes = fs::read(self.on_disk(path))?;
+        let last_modified = fs::metadata(self.on_disk(path))?.modified()?;
+        let hash = content_hash(&bytes);
 
-        let stemmed_tokens = lex.filter_map(|tok| stem(tok, StemmingAlgorithm::Porter2));
+        let mut added = Vec::new();
+        if let Some(fields) = self.records.get(path).cloned() {
+            for record in extract::records(path, &bytes, &fields)? {
+                let doc_path = DocPath::record(path, &record.id);
+                self.insert(&doc_path, &record.content, last_modified, hash);
+                added.push(doc_path);
+            }
+            self.remove_stale(path, &added);
+            return Ok(());
+        }
 
-        // Build out the term frequency map of the document, and the number of tokens it has
-        let (tf, count) =
-            stemmed_tokens.fold((TermFrequency::new(), 0), |(mut tf, mut count), tok| {
-                count += 1;
-                let tok = tok.to_string();
-                if let Some(freq) = tf.get_mut(&tok) {
-                    *freq += 1;
-                } else {
-                    tf.insert(tok, 1);
+        // Sniffed once, for both choosing the extractor and opening archives
+        let mime = extract::mime_type(&bytes);
+        let members = match self.extractors.find_as(path, mime) {
+            Some(_) => None,
+            None => extract::archive_members_as(&bytes, mime)?,
+        };
+
+        match members {
+            Some(members) => {
+                for (name, member) in members {
+                    if let Ok(content) = self.extractors.extract(Path::new(&name), &member) {
+                        let doc_path = DocPath::in_archive(path, &name);
+                        self.insert(&doc_path, &content, last_modified, hash);
+                        added.push(doc_path);
+                    }
+                }
+                if added.is_empty() {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        "No supported files in archive",
+                    ));
                 }
+            }
+            None => {
+                let content = self.extractors.extract_as(path, &bytes, mime)?;
+                let doc_path = DocPath::new(path);
+                self.insert(&doc_path, &content, last_modified, hash);
+                added.push(doc_path);
+            }
+        }
 
-                (tf, count)
-            });
+        self.remove_stale(path, &added);
+        Ok(())
+    }
+
+    /// Indexes each record of a JSON, JSONL or CSV file as its own document,
+    /// found by its id. Returns the number of records.
+    pub fn add_records(&mut self, path: &Path, fields: &RecordFields) -> io::Result<usize> {
+        let path = self.normalize_path(path)?;
+        let previous = self.records.insert(path.clone(), fields.clone());
+
+        let result = self.add_file(&path);
+        if result.is_err() {
+            match previous {
+                Some(fields) => self.records.insert(path.clone(), fields),
+                None => self.records.remove(&path),
+            };
+        }
+        result?;
+
+        Ok(self.documents_in(&path).len())
+    }
+
+    /// Removes the documents from `path` that weren't just added: members
+    /// dropped from an archive, records dropped from a file, or the file
+    /// itself if it used to be read as a single document
+    fn remove_stale(&mut self, path: &Path, added: &[DocPath]) {
+        let added: HashSet<&DocPath> = added.iter().collect();
+        for stale in self.documents_in(path) {
+            if !added.contains(&stale) {
+                self.remove_document(&stale);
+            }
+        }
+    }
 
-        // Now need to update document freq for the model
-        for term in tf.keys() {
-            if let Some(freq) = self.df.get_mut(term) {
-                *freq += 1
-            } else {
-                self.df.insert(term.to_string(), 1);
+    /// Indexes extracted content as the document at `path`
+    fn insert(&mut self, path: &DocPath, content: &Content, last_modified: SystemTime, hash: u64) {
+        // Drop any previous version of the document so its terms aren't counted twice,
+        // keeping its id
+        let id = match self.ids.get(path) {
+            Some(&id) => {
+                self.remove_document(path);
+                id
+            }
+            None => {
+                self.next_id += 1;
+                self.next_id - 1
+            }
+        };
+
+        let language = if self.analyzer.detect_language {
+            language::detect(content.body())
+        } else {
+            None
+        };
+        let stemmer = language.map_or(self.analyzer.stemmer, |l| Some(l.stemmer()));
+
+        let fields = self.index.insert_fields(
+            id,
+            content
+                .fields()
+                .map(|(name, text)| (name, self.analyzer.analyze_with(text, stemmer))),
+        );
+        let count = fields.iter().map(|f| f.len as usize).sum();
+
+        // Pages are analysed on their own to find where each one starts
+        let mut pages = Vec::new();
+        if let Some(body) = fields.iter().find(|f| f.name == BODY) {
+            let mut start = body.start;
+            for page in content.pages() {
+                pages.push(start);
+                // Tokens the filters drop still take up positions
+                let tokens = self.analyzer.tokenizer.tokenize(page);
+                start += tokens.last().map_or(0, |(position, _)| position + 1);
             }
         }
 
+        self.total_len += count;
+        for field in &fields {
+            *self.field_len.entry(field.name.clone()).or_default() += field.len as usize;
+        }
+        self.ids.insert(path.clone(), id);
+        let file = path.fs_path().to_path_buf();
+        self.files.entry(file).or_default().insert(id);
         self.documents.insert(
-            path.to_path_buf(),
+            id,
             Document {
-                tf,
+                path: path.clone(),
                 count,
-                last_modified: SystemTime::now(),
+                fields,
+                pages,
+                last_modified,
+                hash,
+                language,
             },
         );
+    }
 
-        self.save(&PathBuf::from("./output.json"))?;
+    /// Removes a document and its contribution to the document frequencies.
+    /// Returns whether the document was in the model. Once the last record
+    /// of a file is removed, adding the file again indexes it as a whole.
+    pub fn remove(&mut self, path: &DocPath) -> bool {
+        if !self.remove_document(path) {
+            return false;
+        }
 
-        Ok(())
+        let file = path.fs_path();
+        if self.records.contains_key(file) && !self.files.contains_key(file) {
+            self.records.remove(file);
+        }
+        true
+    }
+
+    /// Removes a document, leaving how its file is read as it was, for
+    /// when the file is about to be indexed again
+    fn remove_document(&mut self, path: &DocPath) -> bool {
+        let Some(id) = self.ids.remove(path) else {
+            return false;
+        };
+        if let Some(ids) = self.files.get_mut(path.fs_path()) {
+            ids.remove(&id);
+            if ids.is_empty() {
+                self.files.remove(path.fs_path());
+            }
+        }
+
+        if let Some(doc) = self.documents.remove(&id) {
+            self.total_len -= doc.count;
+            for field in &doc.fields {
+                if let Some(len) = self.field_len.get_mut(&field.name) {
+                    *len -= field.len as usize;
+                }
+            }
+        }
+        self.index.remove(id);
+
+        true
+    }
+
+    /// Paths of every indexed document
+    pub fn paths(&self) -> impl Iterator<Item = &DocPath> {
+        self.ids.keys()
+    }
+
+    /// Every document read from the file at `path`: the file itself, or
+    /// each member if it is an archive
+    fn documents_in(&self, path: &Path) -> Vec<DocPath> {
+        let mut paths: Vec<DocPath> = self
+            .files
+            .get(path)
+            .into_iter()
+            .flatten()
+            .map(|id| self.documents[id].path.clone())
+            .collect();
+        paths.sort();
+        paths
     }
 
-    pub fn remove(&mut self, path: &PathBuf) {
-        if let Some(doc) = self.documents.remove(path) {
-            // go through each of the documents keys
-            for term in doc.tf.keys() {
-                // Decrement in models docfreq
-                if let Some(freq) = self.df.get_mut(term) {
-                    *freq -= 1;
+    /// Brings indexed documents up to date with the filesystem.
+    ///
+    /// Documents whose file was deleted are removed. Files whose mtime
+    /// changed are re-hashed, and only re-extracted if their content differs.
+    /// Archives are checked as a whole, and re-extracted as a whole.
+    pub fn sync(&mut self) -> SyncReport {
+        let mut report = SyncReport::default();
+
+        let files: BTreeSet<PathBuf> = self.files.keys().cloned().collect();
+
+        for path in files {
+            let docs = self.documents_in(&path);
+
+            let modified = match fs::metadata(self.on_disk(&path)).and_then(|meta| meta.modified())
+            {
+                Ok(modified) => modified,
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                    for doc in docs {
+                        self.remove(&doc);
+                        report.removed.push(doc);
+                    }
+                    continue;
+                }
+                Err(e) => {
+                    report.failed.push((path, e));
+                    continue;
+                }
+            };
+
+            // Every document from the same file was indexed from the same content
+            let doc = &self.documents[&self.ids[&docs[0]]];
+            if doc.last_modified == modified {
+                continue;
+            }
+
+            let hash = match fs::read(self.on_disk(&path)) {
+                Ok(bytes) => content_hash(&bytes),
+                Err(e) => {
+                    report.failed.push((path, e));
+                    continue;
                 }
+            };
+
+            // Touched but not changed, so only the timestamp needs updating
+            if doc.hash == hash {
+                for doc in &docs {
+                    if let Some(doc) = self.documents.get_mut(&self.ids[doc]) {
+                        doc.last_modified = modified;
+                    }
+                }
+                continue;
+            }
+
+            // Adding replaces the file's documents, and leaves them be if it fails
+            match self.add_file(&path) {
+                Ok(()) => report.updated.push(path),
+                Err(e) => report.failed.push((path, e)),
             }
         }
+
+        report
     }
 }
 
-fn compute_tf(t: &str, d: &Document) -> f32 {
-    // Total number of terms in document
-    let count = d.count as f32;
+impl Searchable for Model {
+    fn index(&self) -> &InvertedIndex {
+        &self.index
+    }
+
+    fn doc_ids(&self) -> Vec<DocId> {
+        let mut ids: Vec<DocId> = self.documents.keys().copied().collect();
+        ids.sort_unstable();
+        ids
+    }
+
+    fn path(&self, doc: DocId) -> &DocPath {
+        &self.documents[&doc].path
+    }
+
+    fn fields(&self, doc: DocId) -> &[FieldRange] {
+        &self.documents[&doc].fields
+    }
 
-    // Ensure div by 0 does not occur
-    if count == 0.0 {
-        return 0.0;
+    fn language(&self, doc: DocId) -> Option<Language> {
+        self.documents[&doc].language
     }
+}
+
+/// `path` without `.` components, and with each `..` taking off the
+/// component before it, without looking at the filesystem
+fn clean(path: &Path) -> PathBuf {
+    let mut cleaned = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir if cleaned.file_name().is_some() => {
+                cleaned.pop();
+            }
+            component => cleaned.push(component),
+        }
+    }
+    cleaned
+}
 
-    // Number of times t appears in d
-    let freq = *d.tf.get(t).unwrap_or(&0) as f32;
+/// A copy of an error shared by several documents, since errors aren't `Clone`
+fn copy_error(e: &io::Error) -> io::Error {
+    io::Error::new(e.kind(), e.to_string())
+}
 
-    freq / count
+// FNV-1a, so hashes stay stable across Rust versions and runs
+fn content_hash(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+    })
 }
 
-fn compute_idf(n: usize, t: &str, df: &DocumentFrequency) -> f32 {
-    // Total number of documents in the model
-    let n = n as f32;
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ranker::{Bm25, Bm25f, TITLE};
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_readd_replaces_document() {
+        let dir = tempdir().unwrap();
+        let file_path = dir.path().join("test.txt");
+        fs::write(&file_path, "hello world").unwrap();
+
+        let mut model = Model::default();
+        model.add(&file_path).unwrap();
+        model.add(&file_path).unwrap();
+
+        assert_eq!(model.documents.len(), 1);
+        assert_eq!(model.df("hello"), 1);
+
+        fs::write(&file_path, "goodbye world").unwrap();
+        model.add(&file_path).unwrap();
+
+        assert_eq!(model.df("hello"), 0);
+        assert_eq!(model.df("goodby"), 1);
+        assert_eq!(model.df("world"), 1);
+        assert_eq!(model.total_len, 2);
+    }
+
+    #[test]
+    fn test_remove_drops_unused_terms() {
+        let dir = tempdir().unwrap();
+        let a = dir.path().join("a.txt");
+        let b = dir.path().join("b.txt");
+        fs::write(&a, "shared alpha").unwrap();
+        fs::write(&b, "shared beta").unwrap();
+
+        let mut model = Model::default();
+        model.add(&a).unwrap();
+        model.add(&b).unwrap();
 
-    // Total number of document in the model containing t
-    let freq = *df.get(t).unwrap_or(&0) as f32;
+        assert!(model.remove(&DocPath::new(&a)));
+        assert!(!model.remove(&DocPath::new(&a)));
+        assert_eq!(model.df("alpha"), 0);
+        assert_eq!(model.df("share"), 1);
+    }
+
+    #[test]
+    fn test_query_only_matching_documents() {
+        let dir = tempdir().unwrap();
+        let a = dir.path().join("a.txt");
+        let b = dir.path().join("b.txt");
+        let c = dir.path().join("c.txt");
+        fs::write(&a, "rust rust search").unwrap();
+        fs::write(&b, "rust engine").unwrap();
+        fs::write(&c, "unrelated").unwrap();
+
+        let mut model = Model::default();
+        for path in [&a, &b, &c] {
+            model.add(path).unwrap();
+        }
+
+        let results = model.query("rust", &Bm25::default()).unwrap();
+        let paths: Vec<_> = results.iter().map(|result| result.path).collect();
+
+        assert_eq!(paths, vec![&DocPath::new(&a), &DocPath::new(&b)]);
+    }
+
+    /// Text after emoji and other odd characters should still be indexed,
+    /// and CJK text should be found by any run of its characters
+    #[test]
+    fn test_unicode_text() {
+        let dir = tempdir().unwrap();
+        let a = dir.path().join("a.txt");
+        let b = dir.path().join("b.txt");
+        fs::write(&a, "東京都の天気は晴れ 🌞\u{200d} sunny — «warm»").unwrap();
+        fs::write(&b, "京都の天気").unwrap();
+
+        let mut model = Model::default();
+        for path in [&a, &b] {
+            model.add(path).unwrap();
+        }
+
+        let found = |query: &str| -> Vec<&DocPath> {
+            let results = model.query(query, &Bm25::default()).unwrap();
+            results.into_iter().map(|result| result.path).collect()
+        };
+
+        assert_eq!(found("warm"), vec![&DocPath::new(&a)]);
+        assert_eq!(found("東京"), vec![&DocPath::new(&a)]);
+        assert_eq!(found("京都の天気").len(), 2);
+        assert_eq!(found("都"), vec![&DocPath::new(&b), &DocPath::new(&a)]);
+    }
+
+    #[test]
+    fn test_fields_are_weighted() {
+        let dir = tempdir().unwrap();
+        let titled = dir.path().join("titled.md");
+        let body = dir.path().join("body.md");
+        fs::write(&titled, "# Rust\n\nA language guide with examples").unwrap();
+        fs::write(&body, "# Guide\n\nA language guide with rust examples").unwrap();
+
+        let mut model = Model::default();
+        model.add(&body).unwrap();
+        model.add(&titled).unwrap();
+
+        let id = model.ids[&DocPath::new(&titled)];
+        let fields: Vec<_> = model.documents[&id]
+            .fields
+            .iter()
+            .map(|f| (f.name.as_str(), f.len))
+            .collect();
+        assert_eq!(fields, vec![("body", 5), ("title", 1)]);
+        assert_eq!(model.field_len["title"], 2);
+
+        let ranker = Bm25f {
+            weights: HashMap::from([(TITLE.to_string(), 5.0)]),
+            ..Default::default()
+        };
+        let results = model.query("rust", &ranker).unwrap();
+        let paths: Vec<_> = results.iter().map(|result| result.path).collect();
+        assert_eq!(paths, vec![&DocPath::new(&titled), &DocPath::new(&body)]);
 
-    // Ensure inf doesnt get returned
-    if freq == 0.0 {
-        return 0.0;
+        model.remove(&DocPath::new(&titled));
+        assert_eq!(model.field_len["title"], 1);
     }
 
-    (n / freq).log10()
+    #[test]
+    fn test_result_pages() {
+        let dir = tempdir().unwrap();
+        let file_path = dir.path().join("paged.txt");
+        fs::write(&file_path, "alpha beta gamma delta").unwrap();
+
+        let mut model = Model::default();
+        model.add(&file_path).unwrap();
+        let page = |model: &Model, query| model.query(query, &Bm25::default()).unwrap()[0].page;
+        assert_eq!(page(&model, "gamma"), None);
+
+        // As if extracted from a two page PDF
+        let id = model.ids[&DocPath::new(&file_path)];
+        model.documents.get_mut(&id).unwrap().pages = vec![0, 2];
+        assert_eq!(page(&model, "alpha"), Some(1));
+        assert_eq!(page(&model, "gamma"), Some(2));
+        assert_eq!(page(&model, "delta"), Some(2));
+    }
+
+    #[test]
+    fn test_expect_stemmer() {
+        let model = Model::new(Analyzer {
+            stemmer: Some(StemmingAlgorithm::Porter),
+            ..Default::default()
+        });
+
+        assert!(model
+            .expect_stemmer(Some(StemmingAlgorithm::Porter))
+            .is_ok());
+        assert!(model
+            .expect_stemmer(Some(StemmingAlgorithm::Porter2))
+            .is_err());
+        assert!(model.expect_stemmer(None).is_err());
+    }
+
+    #[test]
+    fn test_detect_language() {
+        let dir = tempdir().unwrap();
+        let german = dir.path().join("de.txt");
+        let english = dir.path().join("en.txt");
+        fs::write(
+            &german,
+            "Die Häuser in dieser Straße wurden alle vor über hundert Jahren gebaut",
+        )
+        .unwrap();
+        fs::write(
+            &english,
+            "The houses in this street were all built more than a hundred years ago",
+        )
+        .unwrap();
+
+        let mut model = Model::new(Analyzer {
+            detect_language: true,
+            ..Default::default()
+        });
+        model.add(&german).unwrap();
+        model.add(&english).unwrap();
+
+        let language = |path: &PathBuf| model.documents[&model.ids[&DocPath::new(path)]].language;
+        assert_eq!(language(&german), Some(Language::German));
+        assert_eq!(language(&english), Some(Language::English));
+
+        // Stemmed with the German stemmer, and found from either form
+        assert_eq!(model.df("haus"), 1);
+        for query in ["Haus", "Häuser"] {
+            let results = model.query(query, &Bm25::default()).unwrap();
+            assert_eq!(results.len(), 1);
+            assert_eq!(results[0].path, &DocPath::new(&german));
+        }
+
+        let results = model.query("+built lang:de", &Bm25::default()).unwrap();
+        assert!(results.is_empty());
+    }
+
+    /// An identifier should find the same identifier written in any style,
+    /// but not its parts apart
+    #[test]
+    fn test_identifier_parts() {
+        let dir = tempdir().unwrap();
+        let code = dir.path().join("main.rs");
+        let prose = dir.path().join("notes.txt");
+        fs::write(&code, "fn main() {\n    parse_query(x);\n}\n").unwrap();
+        fs::write(&prose, "parse the query").unwrap();
+
+        let mut model = Model::default();
+        model.add(&code).unwrap();
+        model.add(&prose).unwrap();
+
+        let found = |query: &str| -> Vec<&DocPath> {
+            let results = model.query(query, &Bm25::default()).unwrap();
+            results.into_iter().map(|result| result.path).collect()
+        };
+
+        assert_eq!(found("parseQuery"), vec![&DocPath::new(&code)]);
+        assert_eq!(found("ParseQuery"), vec![&DocPath::new(&code)]);
+        assert_eq!(found("parse_query"), vec![&DocPath::new(&code)]);
+        assert_eq!(found("query").len(), 2);
+        // The parts share the identifier's position, so `x` follows them directly
+        assert_eq!(found("\"parseQuery x\""), vec![&DocPath::new(&code)]);
+    }
+
+    /// Dropped stop words should still count towards phrase and NEAR distances
+    #[test]
+    fn test_stop_word_gaps() {
+        let dir = tempdir().unwrap();
+        let spaced = dir.path().join("spaced.txt");
+        let adjacent = dir.path().join("adjacent.txt");
+        fs::write(&spaced, "the speed of light").unwrap();
+        fs::write(&adjacent, "light speed").unwrap();
+
+        let mut model = Model::new(Analyzer::default().with_stop_words(["the", "of"]));
+        model.add(&spaced).unwrap();
+        model.add(&adjacent).unwrap();
+
+        let found = |query: &str| -> Vec<&DocPath> {
+            let results = model.query(query, &Bm25::default()).unwrap();
+            results.into_iter().map(|result| result.path).collect()
+        };
+
+        assert_eq!(found("\"speed of light\""), vec![&DocPath::new(&spaced)]);
+        assert!(found("\"speed light\"").is_empty());
+        assert_eq!(found("speed NEAR/1 light"), vec![&DocPath::new(&adjacent)]);
+        assert_eq!(found("speed NEAR/2 light").len(), 2);
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let dir = tempdir().unwrap();
+        let file_path = dir.path().join("test.txt");
+        let model_path = dir.path().join("model.json");
+        fs::write(&file_path, "hello world").unwrap();
+
+        let mut model = Model::default();
+        model.add(&file_path).unwrap();
+        model.save(&model_path).unwrap();
+
+        let loaded = Model::from(&model_path).unwrap();
+        assert_eq!(loaded.documents.len(), 1);
+        assert_eq!(loaded.ids, model.ids);
+        assert_eq!(loaded.total_len, model.total_len);
+        assert_eq!(
+            loaded.index.postings("hello"),
+            model.index.postings("hello")
+        );
+
+        // Nothing should be left behind from the atomic write
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 2);
+    }
+
+    #[test]
+    fn test_sync() {
+        let dir = tempdir().unwrap();
+        let kept = dir.path().join("kept.txt");
+        let changed = dir.path().join("changed.txt");
+        let deleted = dir.path().join("deleted.txt");
+        fs::write(&kept, "kept").unwrap();
+        fs::write(&changed, "before").unwrap();
+        fs::write(&deleted, "deleted").unwrap();
+
+        let mut model = Model::default();
+        for path in [&kept, &changed, &deleted] {
+            model.add(path).unwrap();
+        }
+
+        fs::write(&changed, "after").unwrap();
+        // Force the mtime to differ even on coarse-grained filesystems
+        let id = model.ids[&DocPath::new(&changed)];
+        model.documents.get_mut(&id).unwrap().last_modified = SystemTime::UNIX_EPOCH;
+        fs::remove_file(&deleted).unwrap();
+
+        let report = model.sync();
+
+        assert_eq!(report.updated, vec![changed]);
+        assert_eq!(report.removed, vec![DocPath::new(deleted)]);
+        assert!(report.failed.is_empty());
+        assert_eq!(model.df("befor"), 0);
+        assert_eq!(model.df("after"), 1);
+        assert_eq!(model.df("kept"), 1);
+    }
+
+    /// A model saved before the inverted index should load by indexing its
+    /// files again
+    #[test]
+    fn test_legacy_model() {
+        let dir = tempdir().unwrap();
+        let kept = dir.path().join("kept.txt");
+        let deleted = dir.path().join("deleted.txt");
+        fs::write(&kept, "memory safety").unwrap();
+
+        let mtime = serde_json::json!({"secs_since_epoch": 0, "nanos_since_epoch": 0});
+        let legacy = serde_json::json!({
+            "documents": {
+                kept.to_str().unwrap(): {"tf": {"memori": 1}, "count": 2, "last_modified": mtime},
+                deleted.to_str().unwrap(): {"tf": {"gone": 1}, "count": 1, "last_modified": mtime},
+            },
+            "df": {"memori": 1, "gone": 1},
+        });
+        let path = dir.path().join("model.json");
+        fs::write(&path, legacy.to_string()).unwrap();
+
+        let model = Model::from(&path).unwrap();
+
+        // Files next to the model are kept relative to it
+        assert_eq!(
+            model.paths().collect::<Vec<_>>(),
+            vec![&DocPath::new("kept.txt")]
+        );
+        let results = model.query("\"memory safety\"", &Bm25::default()).unwrap();
+        assert_eq!(results.len(), 1);
+    }
+
+    /// Records with the same id in different files should stay apart, and
+    /// be found by their file's path
+    #[test]
+    fn test_records_in_several_files() {
+        let dir = tempdir().unwrap();
+        fs::create_dir(dir.path().join("exports")).unwrap();
+        let exported = dir.path().join("exports").join("a.csv");
+        let other = dir.path().join("b.csv");
+        fs::write(&exported, "id,body\n1,money owed\n").unwrap();
+        fs::write(&other, "id,body\n1,money spent\n").unwrap();
+
+        let mut model = Model::default();
+        for path in [&exported, &other] {
+            model.add_records(path, &RecordFields::default()).unwrap();
+        }
+
+        let results = model.query("money path:exports", &Bm25::default()).unwrap();
+        let paths: Vec<_> = results.iter().map(|r| r.path.to_string()).collect();
+        assert_eq!(paths, vec![format!("{}#1", exported.display())]);
+    }
+
+    /// A file should be one document however its path is written, kept
+    /// relative to the model's directory
+    #[test]
+    fn test_normalized_paths() {
+        let dir = tempdir().unwrap();
+        fs::create_dir(dir.path().join("docs")).unwrap();
+        fs::write(dir.path().join("docs").join("c.txt"), "memory").unwrap();
+
+        let mut model = Model::from(&dir.path().join("model.json")).unwrap();
+        for path in ["docs/c.txt", "docs/./c.txt", "docs/../docs/c.txt"] {
+            model.add(&dir.path().join(path)).unwrap();
+        }
+        model.sync();
+
+        let paths: Vec<_> = model.paths().cloned().collect();
+        assert_eq!(paths, vec![DocPath::new("docs/c.txt")]);
+        assert_eq!(model.query("memory", &Bm25::default()).unwrap().len(), 1);
+        let query = query::parse("memory", model.analyzer()).unwrap();
+        assert!(model.snippets(&paths[0], &query, 1).is_ok());
+
+        // Files elsewhere keep their full path
+        let other = tempdir().unwrap();
+        let outside = other.path().join("d.txt");
+        fs::write(&outside, "memory").unwrap();
+        model.add(&other.path().join(".").join("d.txt")).unwrap();
+        assert!(model.ids.contains_key(&DocPath::new(&outside)));
+    }
+
+    /// A file that can no longer be read should keep its documents
+    #[test]
+    fn test_sync_failure_keeps_documents() {
+        let dir = tempdir().unwrap();
+        let tickets = dir.path().join("tickets.jsonl");
+        fs::write(&tickets, "{\"id\": \"T-1\", \"title\": \"Login crash\"}\n").unwrap();
+
+        let mut model = Model::default();
+        model
+            .add_records(&tickets, &RecordFields::default())
+            .unwrap();
+
+        fs::write(&tickets, "{not json").unwrap();
+        let id = model.ids[&DocPath::record(&tickets, "T-1")];
+        model.documents.get_mut(&id).unwrap().last_modified = SystemTime::UNIX_EPOCH;
+
+        let report = model.sync();
+
+        assert_eq!(report.failed.len(), 1);
+        assert!(report.updated.is_empty());
+        assert_eq!(model.df("login"), 1);
+        assert_eq!(model.documents.len(), 1);
+    }
+
+    #[test]
+    fn test_records() {
+        let dir = tempdir().unwrap();
+        let tickets = dir.path().join("tickets.jsonl");
+        fs::write(
+            &tickets,
+            "{\"id\": \"T-1\", \"title\": \"Login crash\", \"body\": \"Crashes on login\"}\n\
+             {\"id\": \"T-2\", \"title\": \"Slow search\", \"body\": \"Search is slow\"}\n",
+        )
+        .unwrap();
+
+        let mut model = Model::default();
+        let count = model
+            .add_records(&tickets, &RecordFields::default())
+            .unwrap();
+        assert_eq!(count, 2);
+
+        let results = model.query("login", &Bm25::default()).unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].path, &DocPath::record(&tickets, "T-1"));
+
+        // Re-indexing should split the file into records again, dropping old ones
+        fs::write(
+            &tickets,
+            "{\"id\": \"T-2\", \"title\": \"Slow search\", \"body\": \"Still slow\"}\n",
+        )
+        .unwrap();
+        model.add(&tickets).unwrap();
+
+        let paths: Vec<_> = model.paths().cloned().collect();
+        assert_eq!(paths, vec![DocPath::record(&tickets, "T-2")]);
+        assert_eq!(model.df("still"), 1);
+
+        // Without any records left, the file is a plain file again
+        assert!(model.remove(&DocPath::record(&tickets, "T-2")));
+        assert!(model.records.is_empty());
+        model.add(&tickets).unwrap();
+        let paths: Vec<_> = model.paths().cloned().collect();
+        assert_eq!(paths, vec![DocPath::new(&tickets)]);
+    }
+
+    #[test]
+    fn test_snippets() {
+        let dir = tempdir().unwrap();
+        let notes = dir.path().join("notes.md");
+        fs::write(
+            &notes,
+            "# Running\n\nShe kept running until the race was won.",
+        )
+        .unwrap();
+        let tickets = dir.path().join("tickets.jsonl");
+        fs::write(
+            &tickets,
+            "{\"id\": \"T-1\", \"title\": \"Crash\", \"body\": \"Runs out of memory\"}\n\
+             {\"id\": \"T-2\", \"title\": \"Slow\", \"body\": \"Search runs slowly\"}\n",
+        )
+        .unwrap();
+
+        let mut model = Model::default();
+        model.add(&notes).unwrap();
+        model
+            .add_records(&tickets, &RecordFields::default())
+            .unwrap();
+
+        let snippets = |path: &DocPath, query: &str| -> Vec<String> {
+            let query = query::parse(query, model.analyzer()).unwrap();
+            model
+                .snippets(path, &query, 1)
+                .unwrap()
+                .iter()
+                .map(|s| s.render("[", "]"))
+                .collect()
+        };
+
+        let notes = DocPath::new(&notes);
+        assert_eq!(
+            snippets(&notes, "run"),
+            vec!["She kept [running] until the race was won."]
+        );
+        // Without a match in the text, the document opens the passage
+        assert_eq!(
+            snippets(&notes, "ext:md"),
+            vec!["She kept running until the race was won."]
+        );
+
+        let ticket = DocPath::record(&tickets, "T-1");
+        assert_eq!(snippets(&ticket, "memory"), vec!["Runs out of [memory]"]);
+        // Other fields are used when the body doesn't match
+        assert_eq!(snippets(&ticket, "crash"), vec!["[Crash]"]);
+
+        // Several documents at once come back in the order asked for
+        let other = DocPath::record(&tickets, "T-2");
+        let query = query::parse("run", model.analyzer()).unwrap();
+        let found: Vec<_> = model
+            .snippets_for(&[&other, &notes, &ticket], &query, 1)
+            .into_iter()
+            .map(|snippets| snippets.unwrap()[0].render("[", "]"))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                "Search [runs] slowly",
+                "She kept [running] until the race was won.",
+                "[Runs] out of memory",
+            ]
+        );
+    }
+
+    #[cfg(feature = "archive")]
+    #[test]
+    fn test_archive_members() {
+        use crate::extract::archive::tests::{tar_gz, zip};
+
+        let dir = tempdir().unwrap();
+        let bundle = dir.path().join("bundle.zip");
+        fs::write(
+            &bundle,
+            zip(&[
+                ("docs/intro.md", "# Intro\n\nGetting started with rust"),
+                ("notes.txt", "rust notes"),
+                ("logo.png", "\0binary"),
+            ]),
+        )
+        .unwrap();
+        let tarball = dir.path().join("release.tar.gz");
+        fs::write(&tarball, tar_gz(&[("./README", "rust release")])).unwrap();
+
+        let mut model = Model::default();
+        model.add(&bundle).unwrap();
+        model.add(&tarball).unwrap();
+
+        let mut paths: Vec<_> = model.paths().map(DocPath::to_string).collect();
+        paths.sort();
+        let bundle_name = bundle.display().to_string();
+        let tarball_name = tarball.display().to_string();
+        assert_eq!(
+            paths,
+            vec![
+                format!("{}!/docs/intro.md", bundle_name),
+                format!("{}!/notes.txt", bundle_name),
+                format!("{}!/README", tarball_name),
+            ]
+        );
+
+        let results = model.query("rust ext:md", &Bm25::default()).unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].path.member(), Some("docs/intro.md"));
+
+        // Members dropped from the archive should go when it's re-indexed
+        fs::write(&bundle, zip(&[("notes.txt", "updated notes")])).unwrap();
+        let id = model.ids[&DocPath::in_archive(&bundle, "notes.txt")];
+        model.documents.get_mut(&id).unwrap().last_modified = SystemTime::UNIX_EPOCH;
+
+        let report = model.sync();
+
+        assert_eq!(report.updated, vec![bundle.clone()]);
+        assert_eq!(model.documents_in(&bundle).len(), 1);
+        assert_eq!(model.df("start"), 0);
+        assert_eq!(model.df("updat"), 1);
+
+        // An archive without anything to index shouldn't count as added
+        let empty = dir.path().join("images.zip");
+        fs::write(&empty, zip(&[("logo.png", "\0binary")])).unwrap();
+        let err = model.add(&empty).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        assert!(model.documents_in(&empty).is_empty());
+    }
 }
@@ -0,0 +1,1098 @@
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
+
+use crate::analysis::Analyzer;
+use crate::doc_path::DocPath;
+use crate::index::{DocId, FieldRange, InvertedIndex, Posting};
+use crate::language::Language;
+use crate::ranker::{CorpusStats, FieldMatch, Ranker, TermMatch};
+
+// Grammar, from loosest to tightest binding:
+//
+//   group    := clause+                    clauses are optional unless marked or negated
+//   clause   := ("+" | "-")? or
+//   or       := and ("OR" and)*
+//   and      := not ("AND" not)*
+//   not      := "NOT" not | near
+//   near     := primary ("NEAR/n" primary)*
+//   primary  := term | "phrase" | field:value | "(" group ")"
+
+/// A parsed search query
+#[derive(Debug, PartialEq)]
+pub enum Query {
+    /// A single analysed term
+    Term(String),
+    /// Terms that must appear at the same positions relative to each other
+    /// as given, like words that are consecutive and in order
+    Phrase(Vec<(u32, String)>),
+    /// Two clauses no more than `distance` positions apart, in either order
+    Near {
+        left: Box<Query>,
+        right: Box<Query>,
+        distance: u32,
+    },
+    /// Documents matching any of the clauses, scored by the best of them,
+    /// like the same text stemmed for each language
+    Any(Vec<Query>),
+    /// Restricts results by document metadata, without affecting their score
+    Filter(Filter),
+    /// Documents must match every `must` clause and no `must_not` clause.
+    /// Without `must` clauses they must match at least one `should` clause,
+    /// and with only `must_not` clauses every other document matches.
+    Bool {
+        must: Vec<Query>,
+        should: Vec<Query>,
+        must_not: Vec<Query>,
+    },
+}
+
+/// Metadata filters written as `field:value`
+#[derive(Debug, PartialEq)]
+pub enum Filter {
+    /// `path:value` keeps documents whose path contains the value
+    Path(String),
+    /// `ext:value` keeps documents with that file extension
+    Ext(String),
+    /// `lang:value` keeps documents detected as that language
+    Lang(Language),
+}
+
+#[derive(Debug, PartialEq)]
+pub enum QueryError {
+    /// A `"` with no closing `"`, at the given byte offset
+    UnterminatedPhrase(usize),
+    /// A `NEAR/` with a missing or invalid distance
+    InvalidDistance(String),
+    /// An operator without the clause(s) it applies to
+    MissingOperand(String),
+    /// A `(` that is never closed, at the given byte offset
+    UnclosedParen(usize),
+    /// A `)` with no matching `(`, at the given byte offset
+    UnexpectedParen(usize),
+    /// A `()` with nothing searchable inside, at the given byte offset
+    EmptyGroup(usize),
+    /// A `field:` with nothing after it
+    MissingFieldValue(String),
+    /// A `lang:` value that isn't a supported language
+    UnknownLanguage(String),
+}
+
+impl fmt::Display for QueryError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            QueryError::UnterminatedPhrase(offset) => {
+                write!(f, "unterminated phrase starting at byte {}", offset)
+            }
+            QueryError::InvalidDistance(op) => {
+                write!(
+                    f,
+                    "invalid distance in `{}`, expected NEAR/n with n > 0",
+                    op
+                )
+            }
+            QueryError::MissingOperand(op) => write!(f, "`{}` is missing a term", op),
+            QueryError::UnclosedParen(offset) => {
+                write!(f, "`(` at byte {} is never closed", offset)
+            }
+            QueryError::UnexpectedParen(offset) => {
+                write!(f, "`)` at byte {} has no matching `(`", offset)
+            }
+            QueryError::EmptyGroup(offset) => {
+                write!(f, "empty parentheses at byte {}", offset)
+            }
+            QueryError::MissingFieldValue(field) => {
+                write!(f, "`{}` needs a value, e.g. `{}value`", field, field)
+            }
+            QueryError::UnknownLanguage(name) => {
+                write!(
+                    f,
+                    "unknown language `{}`, expected e.g. `en` or `german`",
+                    name
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for QueryError {}
+
+#[derive(Debug, PartialEq)]
+enum Token {
+    Clause(Query),
+    And,
+    Or,
+    Not,
+    Near(u32),
+    Required,
+    Prohibited,
+    Open,
+    Close,
+}
+
+/// Splits a query into tokens, analysing terms and phrases with the same
+/// analyzer as the documents. Each token keeps its source text and byte offset for errors.
+fn tokenize<'a>(
+    input: &'a str,
+    analyzer: &Analyzer,
+) -> Result<Vec<(Token, &'a str, usize)>, QueryError> {
+    let mut tokens = Vec::new();
+    let mut rest = input;
+
+    loop {
+        rest = rest.trim_start();
+        let offset = input.len() - rest.len();
+        let Some(c) = rest.chars().next() else {
+            break;
+        };
+
+        match c {
+            '(' | ')' => {
+                let token = if c == '(' { Token::Open } else { Token::Close };
+                tokens.push((token, &rest[..1], offset));
+                rest = &rest[1..];
+            }
+            '"' => {
+                let (phrase, after) = quoted(rest, offset)?;
+                if let Some(clause) = text_clause(analyzer, phrase) {
+                    let source = &rest[..rest.len() - after.len()];
+                    tokens.push((Token::Clause(clause), source, offset));
+                }
+                rest = after;
+            }
+            // A leading + or - marks whatever directly follows it
+            '+' | '-' if rest[1..].starts_with(|c: char| !c.is_whitespace()) => {
+                let token = if c == '+' {
+                    Token::Required
+                } else {
+                    Token::Prohibited
+                };
+                tokens.push((token, &rest[..1], offset));
+                rest = &rest[1..];
+            }
+            _ => {
+                let end = rest
+                    .find(|c: char| c.is_whitespace() || c == '(' || c == ')' || c == '"')
+                    .unwrap_or(rest.len());
+                let (chunk, after) = rest.split_at(end);
+                rest = after;
+
+                let token = match chunk {
+                    "AND" => Token::And,
+                    "OR" => Token::Or,
+                    "NOT" => Token::Not,
+                    _ => {
+                        if let Some(distance) = chunk.strip_prefix("NEAR/") {
+                            let distance =
+                                distance.parse().ok().filter(|d| *d > 0).ok_or_else(|| {
+                                    QueryError::InvalidDistance(chunk.to_string())
+                                })?;
+                            Token::Near(distance)
+                        } else if let Some((field, value)) = field_filter(chunk) {
+                            let value = if !value.is_empty() {
+                                value
+                            } else if rest.starts_with('"') {
+                                let (value, after) = quoted(rest, input.len() - rest.len())?;
+                                rest = after;
+                                value
+                            } else {
+                                return Err(QueryError::MissingFieldValue(chunk.to_string()));
+                            };
+
+                            Token::Clause(Query::Filter(match field {
+                                "path" => Filter::Path(value.to_string()),
+                                "lang" => Filter::Lang(language(value)?),
+                                _ => Filter::Ext(value.trim_start_matches('.').to_string()),
+                            }))
+                        } else if let Some(clause) = text_clause(analyzer, chunk) {
+                            Token::Clause(clause)
+                        } else {
+                            // Nothing searchable, e.g. a lone symbol
+                            continue;
+                        }
+                    }
+                };
+
+                let source = &input[offset..input.len() - rest.len()];
+                tokens.push((token, source, offset));
+            }
+        }
+    }
+
+    Ok(tokens)
+}
+
+/// The language a `lang:` filter names
+fn language(value: &str) -> Result<Language, QueryError> {
+    Language::from_name(value).ok_or_else(|| QueryError::UnknownLanguage(value.to_string()))
+}
+
+/// Splits a `"quoted"` prefix off `rest`, returning the inside and what follows
+fn quoted(rest: &str, offset: usize) -> Result<(&str, &str), QueryError> {
+    let inner = &rest[1..];
+    let end = inner
+        .find('"')
+        .ok_or(QueryError::UnterminatedPhrase(offset))?;
+
+    Ok((&inner[..end], &inner[end + 1..]))
+}
+
+fn field_filter(chunk: &str) -> Option<(&str, &str)> {
+    let (field, value) = chunk.split_once(':')?;
+    match field {
+        "path" | "ext" | "lang" => Some((field, value)),
+        _ => None,
+    }
+}
+
+/// Text that analyses to several terms, like `"memory safety"` or `std::io`, is a phrase.
+/// When documents may be stemmed differently by language, any of the stemmed forms match,
+/// and only the best one counts towards the score.
+fn text_clause(analyzer: &Analyzer, text: &str) -> Option<Query> {
+    let mut clauses = Vec::new();
+    for stemmer in analyzer.stemmers() {
+        let mut terms: Vec<(u32, String)> = analyzer.analyze_query(text, stemmer).collect();
+        let clause = match terms.len() {
+            0 => continue,
+            1 => Query::Term(terms.remove(0).1),
+            _ => {
+                // Positions count from the first term, past any stop words before it
+                let first = terms[0].0;
+                Query::Phrase(
+                    terms
+                        .into_iter()
+                        .map(|(p, term)| (p - first, term))
+                        .collect(),
+                )
+            }
+        };
+
+        if !clauses.contains(&clause) {
+            clauses.push(clause);
+        }
+    }
+
+    match clauses.len() {
+        0 => None,
+        1 => Some(clauses.remove(0)),
+        _ => Some(Query::Any(clauses)),
+    }
+}
+
+type Tokens<'a> = std::iter::Peekable<std::vec::IntoIter<(Token, &'a str, usize)>>;
+
+struct Parser<'a> {
+    tokens: Tokens<'a>,
+}
+
+/// Parses a query. Clauses are optional by default, `+` and `-` make them
+/// required or prohibited, and field filters always restrict the results.
+pub fn parse(input: &str, analyzer: &Analyzer) -> Result<Query, QueryError> {
+    let mut parser = Parser {
+        tokens: tokenize(input, analyzer)?.into_iter().peekable(),
+    };
+
+    let query = parser.group()?;
+
+    // The only thing that stops a top level group early is a stray `)`
+    match parser.tokens.next() {
+        Some((_, _, offset)) => Err(QueryError::UnexpectedParen(offset)),
+        None => Ok(query),
+    }
+}
+
+impl Parser<'_> {
+    fn group(&mut self) -> Result<Query, QueryError> {
+        let (mut must, mut should, mut must_not) = (Vec::new(), Vec::new(), Vec::new());
+
+        while !matches!(self.tokens.peek(), None | Some((Token::Close, _, _))) {
+            let modifier = self
+                .tokens
+                .next_if(|(t, _, _)| matches!(t, Token::Required | Token::Prohibited));
+
+            match modifier {
+                Some((Token::Required, source, _)) => must.push(self.operand(source, Self::or)?),
+                Some((_, source, _)) => must_not.push(self.operand(source, Self::or)?),
+                None => match self.or()? {
+                    filter @ Query::Filter(_) => must.push(filter),
+                    // A bare `NOT b` excludes, rather than matching everything without b
+                    Query::Bool {
+                        must: m,
+                        should: s,
+                        must_not: mut negated,
+                    } if m.is_empty() && s.is_empty() => must_not.append(&mut negated),
+                    clause => should.push(clause),
+                },
+            }
+        }
+
+        if must.is_empty() && must_not.is_empty() && should.len() == 1 {
+            return Ok(should.remove(0));
+        }
+
+        Ok(Query::Bool {
+            must,
+            should,
+            must_not,
+        })
+    }
+
+    fn or(&mut self) -> Result<Query, QueryError> {
+        let mut clauses = vec![self.and()?];
+        while let Some((_, source, _)) = self.tokens.next_if(|(t, _, _)| *t == Token::Or) {
+            clauses.push(self.operand(source, Self::and)?);
+        }
+
+        if clauses.len() == 1 {
+            return Ok(clauses.remove(0));
+        }
+
+        Ok(Query::Bool {
+            must: Vec::new(),
+            should: clauses,
+            must_not: Vec::new(),
+        })
+    }
+
+    fn and(&mut self) -> Result<Query, QueryError> {
+        let mut clauses = vec![self.not()?];
+        while let Some((_, source, _)) = self.tokens.next_if(|(t, _, _)| *t == Token::And) {
+            clauses.push(self.operand(source, Self::not)?);
+        }
+
+        if clauses.len() == 1 {
+            return Ok(clauses.remove(0));
+        }
+
+        // Fold `a AND NOT b` into one clause, rather than first matching every document without b
+        let (mut must, mut must_not) = (Vec::new(), Vec::new());
+        for clause in clauses {
+            match clause {
+                Query::Bool {
+                    must: m,
+                    should,
+                    must_not: mut negated,
+                } if m.is_empty() && should.is_empty() => must_not.append(&mut negated),
+                clause => must.push(clause),
+            }
+        }
+
+        Ok(Query::Bool {
+            must,
+            should: Vec::new(),
+            must_not,
+        })
+    }
+
+    fn not(&mut self) -> Result<Query, QueryError> {
+        match self.tokens.next_if(|(t, _, _)| *t == Token::Not) {
+            Some((_, source, _)) => Ok(Query::Bool {
+                must: Vec::new(),
+                should: Vec::new(),
+                must_not: vec![self.operand(source, Self::not)?],
+            }),
+            None => self.near(),
+        }
+    }
+
+    fn near(&mut self) -> Result<Query, QueryError> {
+        let mut left = self.primary()?;
+        while let Some((Token::Near(distance), source, _)) =
+            self.tokens.next_if(|(t, _, _)| matches!(t, Token::Near(_)))
+        {
+            left = Query::Near {
+                left: Box::new(left),
+                right: Box::new(self.operand(source, Self::primary)?),
+                distance,
+            };
+        }
+
+        Ok(left)
+    }
+
+    fn primary(&mut self) -> Result<Query, QueryError> {
+        match self.tokens.next() {
+            Some((Token::Clause(clause), _, _)) => Ok(clause),
+            Some((Token::Open, _, offset)) => {
+                let group = self.group()?;
+                if !matches!(self.tokens.next(), Some((Token::Close, _, _))) {
+                    return Err(QueryError::UnclosedParen(offset));
+                }
+
+                match group {
+                    Query::Bool {
+                        must,
+                        should,
+                        must_not,
+                    } if must.is_empty() && should.is_empty() && must_not.is_empty() => {
+                        Err(QueryError::EmptyGroup(offset))
+                    }
+                    group => Ok(group),
+                }
+            }
+            Some((Token::Close, _, offset)) => Err(QueryError::UnexpectedParen(offset)),
+            Some((_, source, _)) => Err(QueryError::MissingOperand(source.to_string())),
+            None => Err(QueryError::MissingOperand(String::new())),
+        }
+    }
+
+    /// Parses the operand of an operator, reporting the operator if it's missing
+    fn operand(
+        &mut self,
+        op: &str,
+        parse: fn(&mut Self) -> Result<Query, QueryError>,
+    ) -> Result<Query, QueryError> {
+        match self.tokens.peek() {
+            None | Some((Token::Close | Token::And | Token::Or | Token::Near(_), _, _)) => {
+                Err(QueryError::MissingOperand(op.to_string()))
+            }
+            _ => parse(self),
+        }
+    }
+}
+
+/// What a query is evaluated against
+pub trait Searchable {
+    fn index(&self) -> &InvertedIndex;
+    /// Every document id, in ascending order
+    fn doc_ids(&self) -> Vec<DocId>;
+    /// Where the document came from
+    fn path(&self, doc: DocId) -> &DocPath;
+    /// Where each field of the document was indexed
+    fn fields(&self, doc: DocId) -> &[FieldRange];
+    /// The language the document was detected as, if any
+    fn language(&self, doc: DocId) -> Option<Language>;
+}
+
+/// Everything needed to find and score matching documents
+pub struct Searcher<'a> {
+    pub source: &'a dyn Searchable,
+    pub ranker: &'a dyn Ranker,
+    pub stats: &'a CorpusStats,
+}
+
+/// A run of matched positions within a document, inclusive on both ends
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Span {
+    pub start: u32,
+    pub end: u32,
+}
+
+/// Every place a query matched in one document, and the resulting score
+#[derive(Debug, PartialEq)]
+pub struct Hit {
+    pub doc: DocId,
+    pub spans: Vec<Span>,
+    pub score: f32,
+}
+
+impl Hit {
+    fn new(doc: DocId, spans: Vec<Span>) -> Self {
+        Self {
+            doc,
+            spans,
+            score: 0.0,
+        }
+    }
+}
+
+impl Query {
+    /// Finds and scores every document the query matches, ordered by document id
+    pub fn evaluate(&self, searcher: &Searcher) -> Vec<Hit> {
+        let index = searcher.source.index();
+
+        match self {
+            Query::Term(term) => {
+                let hits = index.postings(term).iter().map(term_hit).collect();
+                score(searcher, hits)
+            }
+            Query::Phrase(terms) => score(searcher, phrase(index, terms)),
+            Query::Near {
+                left,
+                right,
+                distance,
+            } => {
+                let hits = near(left.evaluate(searcher), right.evaluate(searcher), *distance);
+                score(searcher, hits)
+            }
+            Query::Any(clauses) => any(searcher, clauses),
+            Query::Filter(filter) => searcher
+                .source
+                .doc_ids()
+                .into_iter()
+                .filter(|&doc| filter.is_match(searcher.source, doc))
+                .map(|doc| Hit::new(doc, Vec::new()))
+                .collect(),
+            Query::Bool {
+                must,
+                should,
+                must_not,
+            } => boolean(searcher, must, should, must_not),
+        }
+    }
+
+    /// Every term the query looks for in the text of a document, for
+    /// highlighting matches. Terms the query excludes are left out.
+    pub fn terms(&self) -> HashSet<String> {
+        let mut terms = HashSet::new();
+        self.collect_terms(&mut terms);
+        terms
+    }
+
+    fn collect_terms(&self, terms: &mut HashSet<String>) {
+        match self {
+            Query::Term(term) => {
+                terms.insert(term.clone());
+            }
+            Query::Phrase(words) => terms.extend(words.iter().map(|(_, word)| word.clone())),
+            Query::Near { left, right, .. } => {
+                left.collect_terms(terms);
+                right.collect_terms(terms);
+            }
+            Query::Any(clauses) => {
+                for clause in clauses {
+                    clause.collect_terms(terms);
+                }
+            }
+            Query::Filter(_) => {}
+            Query::Bool { must, should, .. } => {
+                for clause in must.iter().chain(should) {
+                    clause.collect_terms(terms);
+                }
+            }
+        }
+    }
+}
+
+impl Filter {
+    fn is_match(&self, source: &dyn Searchable, doc: DocId) -> bool {
+        let path = source.path(doc);
+        match self {
+            Filter::Path(value) => {
+                path.fs_path().to_string_lossy().contains(value.as_str())
+                    || path.member().is_some_and(|m| m.contains(value.as_str()))
+            }
+            Filter::Ext(value) => path
+                .extension()
+                .is_some_and(|ext| ext.eq_ignore_ascii_case(value)),
+            Filter::Lang(language) => source.language(doc) == Some(*language),
+        }
+    }
+}
+
+/// Scores each hit as a single term occurring once per span, in the field the span starts in
+fn score(searcher: &Searcher, mut hits: Vec<Hit>) -> Vec<Hit> {
+    let df = hits.len();
+    for hit in &mut hits {
+        let fields = searcher.source.fields(hit.doc);
+        let term = TermMatch {
+            df,
+            fields: fields
+                .iter()
+                .map(|field| FieldMatch {
+                    field: &field.name,
+                    tf: hit.spans.iter().filter(|s| field.contains(s.start)).count(),
+                    len: field.len as usize,
+                })
+                .collect(),
+        };
+        hit.score = searcher.ranker.score(&term, searcher.stats);
+    }
+
+    hits
+}
+
+/// Every document any clause matches, with the spans of all of them but the
+/// score of the best
+fn any(searcher: &Searcher, clauses: &[Query]) -> Vec<Hit> {
+    let mut hits: BTreeMap<DocId, Hit> = BTreeMap::new();
+    for clause in clauses {
+        for other in clause.evaluate(searcher) {
+            match hits.get_mut(&other.doc) {
+                Some(hit) => {
+                    let score = hit.score.max(other.score);
+                    merge(hit, other);
+                    hit.score = score;
+                }
+                None => {
+                    hits.insert(other.doc, other);
+                }
+            }
+        }
+    }
+
+    hits.into_values().collect()
+}
+
+fn boolean(searcher: &Searcher, must: &[Query], should: &[Query], must_not: &[Query]) -> Vec<Hit> {
+    let mut hits: BTreeMap<DocId, Hit> = BTreeMap::new();
+
+    if let Some((first, rest)) = must.split_first() {
+        hits.extend(
+            first
+                .evaluate(searcher)
+                .into_iter()
+                .map(|hit| (hit.doc, hit)),
+        );
+        for clause in rest {
+            let mut matched: BTreeMap<_, _> = clause
+                .evaluate(searcher)
+                .into_iter()
+                .map(|hit| (hit.doc, hit))
+                .collect();
+
+            hits.retain(|doc, hit| match matched.remove(doc) {
+                Some(other) => {
+                    merge(hit, other);
+                    true
+                }
+                None => false,
+            });
+        }
+
+        // Optional clauses only add to the score of documents already matched
+        for clause in should {
+            for other in clause.evaluate(searcher) {
+                if let Some(hit) = hits.get_mut(&other.doc) {
+                    merge(hit, other);
+                }
+            }
+        }
+    } else if !should.is_empty() {
+        for clause in should {
+            for other in clause.evaluate(searcher) {
+                match hits.get_mut(&other.doc) {
+                    Some(hit) => merge(hit, other),
+                    None => {
+                        hits.insert(other.doc, other);
+                    }
+                }
+            }
+        }
+    } else if !must_not.is_empty() {
+        let all = searcher.source.doc_ids().into_iter();
+        hits.extend(all.map(|doc| (doc, Hit::new(doc, Vec::new()))));
+    }
+
+    for clause in must_not {
+        for excluded in clause.evaluate(searcher) {
+            hits.remove(&excluded.doc);
+        }
+    }
+
+    hits.into_values().collect()
+}
+
+fn merge(hit: &mut Hit, other: Hit) {
+    hit.score += other.score;
+    hit.spans.extend(other.spans);
+    hit.spans.sort_by_key(|span| (span.start, span.end));
+}
+
+fn term_hit(posting: &Posting) -> Hit {
+    let spans = posting
+        .positions
+        .iter()
+        .map(|&p| Span { start: p, end: p })
+        .collect();
+
+    Hit::new(posting.doc, spans)
+}
+
+fn phrase(index: &InvertedIndex, terms: &[(u32, String)]) -> Vec<Hit> {
+    let Some(((first_position, first), rest)) = terms.split_first() else {
+        return Vec::new();
+    };
+
+    // Start from every occurrence of the first term, and keep the spans
+    // that have the next term of the phrase as far from their start as in
+    // the phrase, extending them to it
+    let mut hits: Vec<Hit> = index.postings(first).iter().map(term_hit).collect();
+    for (position, term) in rest {
+        let offset = position - first_position;
+        hits = intersect(hits, index.postings(term), |hit, posting| {
+            hit.spans
+                .iter()
+                .filter(|span| {
+                    posting
+                        .positions
+                        .binary_search(&(span.start + offset))
+                        .is_ok()
+                })
+                .map(|span| Span {
+                    start: span.start,
+                    end: span.end.max(span.start + offset),
+                })
+                .collect()
+        });
+    }
+
+    hits
+}
+
+fn near(left: Vec<Hit>, right: Vec<Hit>, distance: u32) -> Vec<Hit> {
+    intersect(left, &right, |l, r| {
+        let mut spans = Vec::new();
+        for a in &l.spans {
+            for b in &r.spans {
+                // Positions between the two spans, 0 if they overlap
+                let gap = b
+                    .start
+                    .saturating_sub(a.end)
+                    .max(a.start.saturating_sub(b.end));
+
+                if gap <= distance {
+                    spans.push(Span {
+                        start: a.start.min(b.start),
+                        end: a.end.max(b.end),
+                    });
+                }
+            }
+        }
+        spans
+    })
+}
+
+/// Walks two doc-ordered lists together, keeping documents in both where
+/// `combine` finds at least one matching span
+fn intersect<T: HasDoc>(
+    hits: Vec<Hit>,
+    others: &[T],
+    combine: impl Fn(&Hit, &T) -> Vec<Span>,
+) -> Vec<Hit> {
+    let mut result = Vec::new();
+    let mut others = others.iter().peekable();
+
+    for hit in hits {
+        while others.next_if(|other| other.doc() < hit.doc).is_some() {}
+
+        if let Some(other) = others.peek() {
+            if other.doc() == hit.doc {
+                let spans = combine(&hit, other);
+                if !spans.is_empty() {
+                    result.push(Hit::new(hit.doc, spans));
+                }
+            }
+        }
+    }
+
+    result
+}
+
+trait HasDoc {
+    fn doc(&self) -> DocId;
+}
+
+impl HasDoc for Hit {
+    fn doc(&self) -> DocId {
+        self.doc
+    }
+}
+
+impl HasDoc for Posting {
+    fn doc(&self) -> DocId {
+        self.doc
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ranker::{Bm25, BODY};
+
+    struct Docs {
+        index: InvertedIndex,
+        paths: Vec<DocPath>,
+        languages: Vec<Option<Language>>,
+        fields: Vec<Vec<FieldRange>>,
+    }
+
+    impl Searchable for Docs {
+        fn index(&self) -> &InvertedIndex {
+            &self.index
+        }
+
+        fn doc_ids(&self) -> Vec<DocId> {
+            (0..self.paths.len() as DocId).collect()
+        }
+
+        fn path(&self, doc: DocId) -> &DocPath {
+            &self.paths[doc as usize]
+        }
+
+        fn fields(&self, doc: DocId) -> &[FieldRange] {
+            &self.fields[doc as usize]
+        }
+
+        fn language(&self, doc: DocId) -> Option<Language> {
+            self.languages[doc as usize]
+        }
+    }
+
+    fn docs() -> Docs {
+        let mut index = InvertedIndex::default();
+        let mut paths = Vec::new();
+        let mut languages = Vec::new();
+        let mut fields = Vec::new();
+        let analyzer = Analyzer::default();
+        for (path, text, language) in [
+            (
+                "docs/safety.md",
+                "memory safety is a rust feature",
+                Some(Language::English),
+            ),
+            ("docs/order.txt", "safety of memory in rust", None),
+            (
+                "src/async.txt",
+                "rust has an async runtime",
+                Some(Language::English),
+            ),
+        ] {
+            let terms = analyzer.analyze(text);
+            fields.push(index.insert_fields(paths.len() as DocId, [(BODY, terms)]));
+            paths.push(DocPath::from(path));
+            languages.push(language);
+        }
+
+        Docs {
+            index,
+            paths,
+            languages,
+            fields,
+        }
+    }
+
+    fn search(query: &str) -> Vec<Hit> {
+        evaluate(&parse(query).unwrap())
+    }
+
+    fn evaluate(query: &Query) -> Vec<Hit> {
+        let docs = docs();
+        let stats = CorpusStats {
+            documents: 3,
+            avg_len: 5.0,
+            ..Default::default()
+        };
+        let searcher = Searcher {
+            source: &docs,
+            ranker: &Bm25::default(),
+            stats: &stats,
+        };
+
+        query.evaluate(&searcher)
+    }
+
+    fn matched(query: &str) -> Vec<DocId> {
+        search(query).iter().map(|hit| hit.doc).collect()
+    }
+
+    fn parse(query: &str) -> Result<Query, QueryError> {
+        super::parse(query, &Analyzer::default())
+    }
+
+    fn term(t: &str) -> Query {
+        Query::Term(t.to_string())
+    }
+
+    fn phrase(words: &[&str]) -> Query {
+        Query::Phrase((0..).zip(words.iter().map(|w| w.to_string())).collect())
+    }
+
+    /// Should split out phrases and analyse their terms
+    #[test]
+    fn parse_phrase() {
+        assert_eq!(
+            parse("\"Memory Safety\" rust").unwrap(),
+            Query::Bool {
+                must: vec![],
+                should: vec![phrase(&["memori", "safeti"]), term("rust"),],
+                must_not: vec![],
+            }
+        );
+    }
+
+    /// Should bind NEAR to the clauses either side of it
+    #[test]
+    fn parse_near() {
+        assert_eq!(
+            parse("rust NEAR/3 \"async runtime\"").unwrap(),
+            Query::Near {
+                left: Box::new(term("rust")),
+                right: Box::new(phrase(&["async", "runtim"])),
+                distance: 3,
+            }
+        );
+    }
+
+    /// AND should bind tighter than OR, and NOT tighter than AND
+    #[test]
+    fn parse_precedence() {
+        assert_eq!(
+            parse("a OR b AND NOT c").unwrap(),
+            Query::Bool {
+                must: vec![],
+                should: vec![
+                    term("a"),
+                    Query::Bool {
+                        must: vec![term("b")],
+                        should: vec![],
+                        must_not: vec![term("c")],
+                    },
+                ],
+                must_not: vec![],
+            }
+        );
+    }
+
+    /// Should sort clauses by their modifiers, with filters always required
+    #[test]
+    fn parse_modifiers_and_filters() {
+        assert_eq!(
+            parse("+rust -(async OR runtime) memory ext:.md path:\"my docs\"").unwrap(),
+            Query::Bool {
+                must: vec![
+                    term("rust"),
+                    Query::Filter(Filter::Ext("md".to_string())),
+                    Query::Filter(Filter::Path("my docs".to_string())),
+                ],
+                should: vec![term("memori")],
+                must_not: vec![Query::Bool {
+                    must: vec![],
+                    should: vec![term("async"), term("runtim")],
+                    must_not: vec![],
+                }],
+            }
+        );
+    }
+
+    /// Should reject malformed queries
+    #[test]
+    fn parse_errors() {
+        let error = |query| parse(query).unwrap_err();
+
+        assert_eq!(error("rust \"memory"), QueryError::UnterminatedPhrase(5));
+        assert_eq!(
+            error("a NEAR/x b"),
+            QueryError::InvalidDistance("NEAR/x".to_string())
+        );
+        assert_eq!(
+            error("a NEAR/0 b"),
+            QueryError::InvalidDistance("NEAR/0".to_string())
+        );
+        assert_eq!(
+            error("NEAR/2 b"),
+            QueryError::MissingOperand("NEAR/2".to_string())
+        );
+        assert_eq!(
+            error("a AND"),
+            QueryError::MissingOperand("AND".to_string())
+        );
+        assert_eq!(
+            error("a OR AND b"),
+            QueryError::MissingOperand("OR".to_string())
+        );
+        assert_eq!(error("NOT"), QueryError::MissingOperand("NOT".to_string()));
+        assert_eq!(error("(a OR b"), QueryError::UnclosedParen(0));
+        assert_eq!(error("a) b"), QueryError::UnexpectedParen(1));
+        assert_eq!(error("a ()"), QueryError::EmptyGroup(2));
+        assert_eq!(
+            error("path:"),
+            QueryError::MissingFieldValue("path:".to_string())
+        );
+        assert_eq!(
+            error("lang:klingon"),
+            QueryError::UnknownLanguage("klingon".to_string())
+        );
+    }
+
+    /// With language detection, terms should match any language's stemmed form
+    #[test]
+    fn parse_every_language() {
+        let analyzer = Analyzer {
+            detect_language: true,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            super::parse("Häuser", &analyzer).unwrap(),
+            Query::Any(vec![term("häuser"), term("haus"), term("häus")])
+        );
+        // Stemmers that agree shouldn't repeat the term
+        assert_eq!(super::parse("rust", &analyzer).unwrap(), term("rust"));
+    }
+
+    /// A document matching several forms of the same text should score as
+    /// its best match, not their sum
+    #[test]
+    fn search_any() {
+        let single = evaluate(&term("memori"));
+        let forms = evaluate(&Query::Any(vec![term("memori"), term("safeti")]));
+
+        let docs: Vec<_> = forms.iter().map(|hit| hit.doc).collect();
+        assert_eq!(docs, vec![0, 1]);
+        let best = evaluate(&term("safeti"));
+        assert_eq!(forms[0].score, single[0].score.max(best[0].score));
+    }
+
+    /// Phrases should only match the words in order and next to each other
+    #[test]
+    fn search_phrase() {
+        assert_eq!(matched("\"memory safety\""), vec![0]);
+        assert_eq!(matched("rust \"memory safety\""), vec![0, 1, 2]);
+    }
+
+    /// NEAR should match in either order within the distance
+    #[test]
+    fn search_near() {
+        assert_eq!(matched("memory NEAR/1 rust"), Vec::<DocId>::new());
+        assert_eq!(matched("memory NEAR/2 rust"), vec![1]);
+        assert_eq!(matched("memory NEAR/4 rust"), vec![0, 1]);
+    }
+
+    /// Boolean operators should combine the matches of their clauses
+    #[test]
+    fn search_boolean() {
+        assert_eq!(matched("memory AND async"), Vec::<DocId>::new());
+        assert_eq!(matched("memory OR async"), vec![0, 1, 2]);
+        assert_eq!(matched("rust -memory"), vec![2]);
+        assert_eq!(matched("NOT memory"), vec![2]);
+        assert_eq!(matched("rust NOT memory"), vec![2]);
+        assert_eq!(matched("rust NOT memory NOT async"), Vec::<DocId>::new());
+        assert_eq!(matched("+memory feature"), vec![0, 1]);
+        assert_eq!(matched("(feature OR async) AND rust"), vec![0, 2]);
+        assert_eq!(matched(""), Vec::<DocId>::new());
+    }
+
+    /// Field filters should restrict results by path and language
+    #[test]
+    fn search_filters() {
+        assert_eq!(matched("rust path:docs/"), vec![0, 1]);
+        assert_eq!(matched("rust ext:md"), vec![0]);
+        assert_eq!(matched("ext:txt"), vec![1, 2]);
+        assert_eq!(matched("rust lang:en"), vec![0, 2]);
+    }
+
+    /// Optional clauses should add to the score of required matches
+    #[test]
+    fn optional_clauses_add_score() {
+        let hits = search("+rust feature");
+
+        assert_eq!(hits.len(), 3);
+        assert!(hits[0].score > hits[1].score);
+    }
+
+    /// Terms should come from every clause a match can use, but not from
+    /// excluded clauses or filters
+    #[test]
+    fn terms() {
+        let query =
+            parse("+\"memory safety\" (rust OR go) -async ext:md runtime NEAR/3 speed").unwrap();
+
+        let mut terms: Vec<_> = query.terms().into_iter().collect();
+        terms.sort();
+
+        assert_eq!(
+            terms,
+            vec!["go", "memori", "runtim", "rust", "safeti", "speed"]
+        );
+    }
+}
@@ -0,0 +1,298 @@
+use std::collections::HashMap;
+
+/// The field every extractor fills with the document's main text
+pub const BODY: &str = "body";
+/// The document's title, for formats that have one
+pub const TITLE: &str = "title";
+/// Section headings, for formats that have them
+pub const HEADING: &str = "heading";
+/// The author from the document's metadata
+pub const AUTHOR: &str = "author";
+/// Comments in source code
+pub const COMMENT: &str = "comment";
+/// String literals in source code
+pub const STRING: &str = "string";
+/// Identifiers in source code, without the language's keywords
+pub const IDENTIFIER: &str = "identifier";
+
+/// Corpus wide statistics needed by the rankers
+#[derive(Debug, Default)]
+pub struct CorpusStats {
+    /// The number of documents in the model
+    pub documents: usize,
+    /// Average number of tokens per document
+    pub avg_len: f32,
+    /// Average number of tokens per document in each field
+    pub avg_field_len: HashMap<String, f32>,
+}
+
+/// How often a term occurs in one field of a document
+#[derive(Debug)]
+pub struct FieldMatch<'a> {
+    pub field: &'a str,
+    /// The number of times the term appears in the field
+    pub tf: usize,
+    /// The total number of tokens in the field
+    pub len: usize,
+}
+
+/// Everything known about one query term in one document
+#[derive(Debug)]
+pub struct TermMatch<'a> {
+    /// The number of documents in the model containing the term
+    pub df: usize,
+    /// The term's frequency in each field of the document
+    pub fields: Vec<FieldMatch<'a>>,
+}
+
+impl TermMatch<'_> {
+    /// Occurrences of the term across all fields
+    pub fn tf(&self) -> usize {
+        self.fields.iter().map(|f| f.tf).sum()
+    }
+
+    /// Length of the document across all fields
+    pub fn doc_len(&self) -> usize {
+        self.fields.iter().map(|f| f.len).sum()
+    }
+}
+
+/// Scores how relevant a document is to a single query term.
+/// A document's rank for a query is the sum of its scores for each term.
+pub trait Ranker {
+    fn score(&self, term: &TermMatch, corpus: &CorpusStats) -> f32;
+}
+
+/// Classic TF×IDF, with TF normalised by document length
+#[derive(Debug, Default, Clone, Copy)]
+pub struct TfIdf;
+
+impl Ranker for TfIdf {
+    fn score(&self, term: &TermMatch, corpus: &CorpusStats) -> f32 {
+        // Ensure div by 0 and log of 0 do not occur
+        let len = term.doc_len();
+        if len == 0 || term.df == 0 {
+            return 0.0;
+        }
+
+        let tf = term.tf() as f32 / len as f32;
+        let idf = (corpus.documents as f32 / term.df as f32).log10();
+
+        tf * idf
+    }
+}
+
+/// Okapi BM25
+#[derive(Debug, Clone, Copy)]
+pub struct Bm25 {
+    /// Term frequency saturation
+    pub k1: f32,
+    /// How strongly scores are normalised by document length
+    pub b: f32,
+}
+
+impl Default for Bm25 {
+    fn default() -> Self {
+        Self { k1: 1.2, b: 0.75 }
+    }
+}
+
+impl Ranker for Bm25 {
+    fn score(&self, term: &TermMatch, corpus: &CorpusStats) -> f32 {
+        let tf = term.tf() as f32;
+        if tf == 0.0 {
+            return 0.0;
+        }
+
+        let norm = length_norm(self.b, term.doc_len() as f32, corpus.avg_len);
+        idf(corpus.documents, term.df) * tf * (self.k1 + 1.0) / (tf + self.k1 * norm)
+    }
+}
+
+/// BM25F: BM25 over a weighted combination of fields, each with its own length normalisation
+#[derive(Debug, Clone)]
+pub struct Bm25f {
+    /// Term frequency saturation
+    pub k1: f32,
+    /// Length normalisation used for fields without an entry in `field_b`
+    pub b: f32,
+    /// Boost for each field, defaulting to 1.0
+    pub weights: HashMap<String, f32>,
+    /// Length normalisation for each field
+    pub field_b: HashMap<String, f32>,
+}
+
+impl Default for Bm25f {
+    fn default() -> Self {
+        Self {
+            k1: 1.2,
+            b: 0.75,
+            weights: HashMap::new(),
+            field_b: HashMap::new(),
+        }
+    }
+}
+
+impl Ranker for Bm25f {
+    fn score(&self, term: &TermMatch, corpus: &CorpusStats) -> f32 {
+        // Combine the length normalised frequencies of every field into a single pseudo-frequency
+        let tf = term.fields.iter().fold(0.0, |tf, field| {
+            // An empty field can't match, and with b = 1 its norm would be 0
+            if field.tf == 0 {
+                return tf;
+            }
+            let weight = self.weights.get(field.field).copied().unwrap_or(1.0);
+            let b = self.field_b.get(field.field).copied().unwrap_or(self.b);
+            let avg_len = corpus
+                .avg_field_len
+                .get(field.field)
+                .copied()
+                .unwrap_or(0.0);
+
+            tf + weight * field.tf as f32 / length_norm(b, field.len as f32, avg_len)
+        });
+
+        if tf == 0.0 {
+            return 0.0;
+        }
+
+        idf(corpus.documents, term.df) * tf / (self.k1 + tf)
+    }
+}
+
+// The BM25 IDF, which stays positive even for terms in most documents
+fn idf(n: usize, df: usize) -> f32 {
+    let (n, df) = (n as f32, df as f32);
+    (1.0 + (n - df + 0.5) / (df + 0.5)).ln()
+}
+
+fn length_norm(b: f32, len: f32, avg_len: f32) -> f32 {
+    // An empty corpus has no meaningful average, so don't normalise
+    if avg_len == 0.0 {
+        return 1.0;
+    }
+
+    1.0 - b + b * len / avg_len
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn corpus() -> CorpusStats {
+        CorpusStats {
+            documents: 10,
+            avg_len: 100.0,
+            avg_field_len: HashMap::from([(BODY.to_string(), 90.0), ("title".to_string(), 10.0)]),
+        }
+    }
+
+    fn body_match(tf: usize, len: usize, df: usize) -> TermMatch<'static> {
+        TermMatch {
+            df,
+            fields: vec![FieldMatch {
+                field: BODY,
+                tf,
+                len,
+            }],
+        }
+    }
+
+    /// A missing term should never add to the score
+    #[test]
+    fn no_match_scores_zero() {
+        let term = body_match(0, 100, 2);
+
+        assert_eq!(TfIdf.score(&term, &corpus()), 0.0);
+        assert_eq!(Bm25::default().score(&term, &corpus()), 0.0);
+        assert_eq!(Bm25f::default().score(&term, &corpus()), 0.0);
+    }
+
+    /// TF-IDF should multiply, not add, the two components
+    #[test]
+    fn tfidf_is_multiplicative() {
+        let score = TfIdf.score(&body_match(10, 100, 1), &corpus());
+
+        assert!((score - 0.1).abs() < 1e-6);
+    }
+
+    /// BM25 should saturate with term frequency and prefer shorter documents
+    #[test]
+    fn bm25_saturates_and_normalises() {
+        let bm25 = Bm25::default();
+
+        let once = bm25.score(&body_match(1, 100, 2), &corpus());
+        let many = bm25.score(&body_match(50, 100, 2), &corpus());
+        let short = bm25.score(&body_match(1, 10, 2), &corpus());
+
+        assert!(many > once);
+        assert!(many < once * (bm25.k1 + 1.0));
+        assert!(short > once);
+    }
+
+    /// Rarer terms should be worth more
+    #[test]
+    fn bm25_prefers_rare_terms() {
+        let bm25 = Bm25::default();
+
+        let rare = bm25.score(&body_match(1, 100, 1), &corpus());
+        let common = bm25.score(&body_match(1, 100, 9), &corpus());
+
+        assert!(rare > common);
+    }
+
+    /// An empty field shouldn't make the score NaN, even without a b to soften its norm
+    #[test]
+    fn bm25f_empty_field() {
+        let term = TermMatch {
+            df: 2,
+            fields: vec![
+                FieldMatch {
+                    field: "title",
+                    tf: 0,
+                    len: 0,
+                },
+                FieldMatch {
+                    field: BODY,
+                    tf: 1,
+                    len: 90,
+                },
+            ],
+        };
+        let bm25f = Bm25f {
+            b: 1.0,
+            ..Default::default()
+        };
+
+        assert!(bm25f.score(&term, &corpus()) > 0.0);
+        assert_eq!(bm25f.score(&body_match(0, 0, 2), &corpus()), 0.0);
+    }
+
+    /// Boosting a field should boost matches in it
+    #[test]
+    fn bm25f_weights_fields() {
+        let term = TermMatch {
+            df: 2,
+            fields: vec![
+                FieldMatch {
+                    field: "title",
+                    tf: 1,
+                    len: 10,
+                },
+                FieldMatch {
+                    field: BODY,
+                    tf: 0,
+                    len: 90,
+                },
+            ],
+        };
+
+        let plain = Bm25f::default();
+        let boosted = Bm25f {
+            weights: HashMap::from([("title".to_string(), 3.0)]),
+            ..Default::default()
+        };
+
+        assert!(boosted.score(&term, &corpus()) > plain.score(&term, &corpus()));
+    }
+}
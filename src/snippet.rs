@@ -0,0 +1,303 @@
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet};
+use std::ops::Range;
+
+use crate::analysis::Analyzer;
+use crate::snowball::StemmingAlgorithm;
+
+/// How many terms a passage spans
+const WINDOW: usize = 24;
+
+/// How many terms of context come before the first match in a passage
+const LEAD: usize = 5;
+
+/// A passage of a document, with the matched words marked
+#[derive(Debug, Clone, PartialEq)]
+pub struct Snippet {
+    /// The passage, with runs of whitespace collapsed to single spaces and
+    /// `…` where it was cut from the surrounding text
+    pub text: String,
+    /// The line of the extracted text the passage starts on, from 1
+    pub line: usize,
+    /// The byte ranges of `text` that matched, in order and not overlapping
+    pub highlights: Vec<Range<usize>>,
+}
+
+impl Snippet {
+    /// The passage with each match wrapped in `open` and `close`, like ANSI
+    /// escapes for a terminal or markers for other programs
+    pub fn render(&self, open: &str, close: &str) -> String {
+        let mut rendered = String::new();
+        let mut end = 0;
+        for range in &self.highlights {
+            rendered.push_str(&self.text[end..range.start]);
+            rendered.push_str(open);
+            rendered.push_str(&self.text[range.clone()]);
+            rendered.push_str(close);
+            end = range.end;
+        }
+        rendered.push_str(&self.text[end..]);
+        rendered
+    }
+}
+
+/// Finds up to `max` passages of `text` that best match `terms`, best first.
+///
+/// The text is analysed the same way it was indexed, so a word matches when
+/// it comes out as one of the query's terms, and `running` is highlighted
+/// for a search for `run`. Passages with more of the distinct terms win,
+/// then those with more matches, and no two passages overlap. Text without
+/// any matches has no passages.
+pub fn snippets(
+    text: &str,
+    terms: &HashSet<String>,
+    analyzer: &Analyzer,
+    stemmer: Option<StemmingAlgorithm>,
+    max: usize,
+) -> Vec<Snippet> {
+    let tokens: Vec<_> = analyzer.analyze_spans(text, stemmer).collect();
+    let matches: Vec<usize> = (0..tokens.len())
+        .filter(|&i| terms.contains(&tokens[i].value))
+        .collect();
+
+    // Every passage starting a little before a match, with its score. Both
+    // ends of the passages only move forward, so the matches inside are
+    // counted by sliding over them rather than searching each passage.
+    let mut candidates = Vec::with_capacity(matches.len());
+    let mut counts: HashMap<&str, usize> = HashMap::new();
+    let (mut first, mut last) = (0, 0);
+    for &i in &matches {
+        let start = i.saturating_sub(LEAD);
+        let window = start..(start + WINDOW).min(tokens.len());
+        while last < matches.len() && matches[last] < window.end {
+            *counts.entry(&tokens[matches[last]].value).or_default() += 1;
+            last += 1;
+        }
+        while matches[first] < window.start {
+            let term = tokens[matches[first]].value.as_str();
+            if let Some(count) = counts.get_mut(term) {
+                *count -= 1;
+                if *count == 0 {
+                    counts.remove(term);
+                }
+            }
+            first += 1;
+        }
+        candidates.push(((counts.len(), last - first), window));
+    }
+    // Stable, so ties go to the earlier passage
+    candidates.sort_by_key(|(score, _)| Reverse(*score));
+
+    let mut chosen: Vec<Range<usize>> = Vec::new();
+    for (_, window) in candidates {
+        if chosen.len() == max {
+            break;
+        }
+        if chosen
+            .iter()
+            .all(|c| window.end <= c.start || c.end <= window.start)
+        {
+            chosen.push(window);
+        }
+    }
+
+    chosen
+        .into_iter()
+        .map(|window| {
+            let highlights = matches
+                .iter()
+                .filter(|m| window.contains(m))
+                .map(|&m| tokens[m].span.clone());
+            let first = &tokens[window.start];
+            let bytes = first.span.start..tokens[window.end - 1].span.end;
+            passage(text, bytes, first.line, highlights)
+        })
+        .collect()
+}
+
+/// The opening passage of `text`, for documents that matched on something
+/// other than their text, or `None` if it has no terms at all
+pub fn lead(
+    text: &str,
+    analyzer: &Analyzer,
+    stemmer: Option<StemmingAlgorithm>,
+) -> Option<Snippet> {
+    let tokens: Vec<_> = analyzer.analyze_spans(text, stemmer).take(WINDOW).collect();
+    let end = tokens.iter().map(|tok| tok.span.end).max()?;
+
+    let start = text.len() - text.trim_start().len();
+    Some(passage(
+        text,
+        start..end,
+        tokens[0].line,
+        std::iter::empty(),
+    ))
+}
+
+/// Cuts `bytes`, which start on `line`, out of `text` as a snippet
+/// highlighting `highlights`
+fn passage(
+    text: &str,
+    mut bytes: Range<usize>,
+    line: usize,
+    highlights: impl Iterator<Item = Range<usize>>,
+) -> Snippet {
+    let has_words = |s: &str| s.chars().any(char::is_alphanumeric);
+    // Keep the punctuation that ends the text rather than marking it as cut
+    if !has_words(&text[bytes.end..]) {
+        bytes.end = text.trim_end().len().max(bytes.end);
+    }
+
+    let mut snippet = String::new();
+    if has_words(&text[..bytes.start]) {
+        snippet.push('…');
+    }
+
+    // Where each byte of the passage ended up once whitespace is collapsed
+    let mut offsets = Vec::with_capacity(bytes.len() + 1);
+    let mut in_space = false;
+    for (i, c) in text[bytes.clone()].char_indices() {
+        offsets.resize(i, snippet.len());
+        offsets.push(snippet.len());
+        if c.is_whitespace() {
+            if !in_space {
+                snippet.push(' ');
+            }
+            in_space = true;
+        } else {
+            snippet.push(c);
+            in_space = false;
+        }
+    }
+    offsets.resize(bytes.len() + 1, snippet.len());
+
+    if has_words(&text[bytes.end..]) {
+        snippet.push('…');
+    }
+
+    // Split identifiers match inside whole words, so ranges may overlap
+    let mut merged: Vec<Range<usize>> = Vec::new();
+    let mut highlights: Vec<_> = highlights
+        .map(|r| offsets[r.start - bytes.start]..offsets[r.end - bytes.start])
+        .collect();
+    highlights.sort_by_key(|r| (r.start, r.end));
+    for range in highlights {
+        match merged.last_mut() {
+            Some(last) if range.start <= last.end => last.end = last.end.max(range.end),
+            _ => merged.push(range),
+        }
+    }
+
+    Snippet {
+        text: snippet,
+        line,
+        highlights: merged,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn terms(query: &str, analyzer: &Analyzer) -> HashSet<String> {
+        analyzer.analyze(query).map(|(_, term)| term).collect()
+    }
+
+    fn rendered(snippets: &[Snippet]) -> Vec<String> {
+        snippets.iter().map(|s| s.render("[", "]")).collect()
+    }
+
+    /// Matches should be found through stemming, and whitespace collapsed
+    #[test]
+    fn stemmed_matches() {
+        let analyzer = Analyzer::default();
+        let text = "She was running\n\n  late, so she ran.";
+
+        let snippets = snippets(
+            text,
+            &terms("run", &analyzer),
+            &analyzer,
+            analyzer.stemmer,
+            1,
+        );
+
+        assert_eq!(
+            rendered(&snippets),
+            vec!["She was [running] late, so she ran."]
+        );
+        assert_eq!(snippets[0].highlights, vec![8..15]);
+        assert_eq!(snippets[0].line, 1);
+    }
+
+    /// The passage with the most distinct terms should come first, cut from
+    /// the text around it
+    #[test]
+    fn best_passage_first() {
+        let analyzer = Analyzer::default();
+        let filler = "lorem ipsum dolor sit amet ".repeat(10);
+        let text = format!("memory {filler}memory safety {filler}memory");
+
+        let snippets = snippets(
+            &text,
+            &terms("memory safety", &analyzer),
+            &analyzer,
+            analyzer.stemmer,
+            2,
+        );
+
+        assert_eq!(snippets.len(), 2);
+        assert!(snippets[0].text.starts_with('…'));
+        assert!(snippets[0].text.ends_with('…'));
+        assert!(snippets[0].render("[", "]").contains("[memory] [safety]"));
+        assert!(snippets[1].render("[", "]").starts_with("[memory] lorem"));
+    }
+
+    /// The parts of a split identifier should highlight within it
+    #[test]
+    fn identifier_parts() {
+        let analyzer = Analyzer::default();
+
+        let snippets = snippets(
+            "call parse_query() first",
+            &terms("query parse", &analyzer),
+            &analyzer,
+            analyzer.stemmer,
+            1,
+        );
+
+        assert_eq!(rendered(&snippets), vec!["call [parse]_[query]() first"]);
+    }
+
+    /// Text matching all over shouldn't take time quadratic in the matches
+    #[test]
+    fn many_matches() {
+        let analyzer = Analyzer::default();
+        let text = format!("{}memory safety", "memory ".repeat(50_000));
+
+        let snippets = snippets(
+            &text,
+            &terms("memory safety", &analyzer),
+            &analyzer,
+            analyzer.stemmer,
+            2,
+        );
+
+        assert_eq!(snippets.len(), 2);
+        assert!(snippets[0].render("[", "]").ends_with("[memory] [safety]"));
+    }
+
+    #[test]
+    fn no_matches() {
+        let analyzer = Analyzer::default();
+        let text = "\n  Rust has an async runtime";
+
+        let found = snippets(text, &terms("memory", &analyzer), &analyzer, None, 1);
+        assert!(found.is_empty());
+
+        let lead = lead(text, &analyzer, None).unwrap();
+        assert_eq!(lead.text, "Rust has an async runtime");
+        assert_eq!(lead.line, 2);
+        assert!(lead.highlights.is_empty());
+        assert_eq!(super::lead("", &analyzer, None), None);
+    }
+}
@@ -1,2 +1,5 @@
+pub mod french;
+pub mod german;
 pub mod porter2;
-pub mod porter;
\ No newline at end of file
+pub mod porter;
+pub mod spanish;
@@ -1,5 +1,6 @@
 //! Generated by Snowball 2.2.0 - https://snowballstem.org/
 
+#![allow(clippy::all)]
 #![allow(non_snake_case)]
 #![allow(non_upper_case_globals)]
 #![allow(unused_mut)]
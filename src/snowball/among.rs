@@ -1,6 +1,11 @@
 use crate::snowball::SnowballEnv;
 
-pub struct Among<T: 'static>(pub &'static str,
-                             pub i32,
-                             pub i32,
-                             pub Option<&'static (dyn Fn(&mut SnowballEnv, &mut T) -> bool + Sync)>);
\ No newline at end of file
+/// Routine run when an `Among` entry matches
+pub type AmongFn<T> = dyn Fn(&mut SnowballEnv, &mut T) -> bool + Sync;
+
+pub struct Among<T: 'static>(
+    pub &'static str,
+    pub i32,
+    pub i32,
+    pub Option<&'static AmongFn<T>>,
+);
@@ -2,12 +2,32 @@ pub mod algorithms;
 mod among;
 mod snowball_env;
 
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
 pub use crate::snowball::among::Among;
 pub use crate::snowball::snowball_env::SnowballEnv;
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum StemmingAlgorithm {
     Porter,
     Porter2,
+    German,
+    French,
+    Spanish,
+}
+
+impl fmt::Display for StemmingAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StemmingAlgorithm::Porter => write!(f, "porter"),
+            StemmingAlgorithm::Porter2 => write!(f, "porter2"),
+            StemmingAlgorithm::German => write!(f, "german"),
+            StemmingAlgorithm::French => write!(f, "french"),
+            StemmingAlgorithm::Spanish => write!(f, "spanish"),
+        }
+    }
 }
 
-pub mod stem;
\ No newline at end of file
+pub mod stem;
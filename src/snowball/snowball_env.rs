@@ -79,12 +79,11 @@ impl<'a> SnowballEnv<'a> {
     /// Check if 's' is before cursor
     /// If so, move cursor to the beginning of s
     pub fn eq_s_b(&mut self, s: &str) -> bool {
-        if (self.cursor - self.limit_backward) < s.len() as i32 {
-            false
-            // Check if cursor -s.len is a char boundary. if not well... return false obv
-        } else if !self
-            .current
-            .is_char_boundary(self.cursor as usize - s.len())
+        // Also check if cursor - s.len is a char boundary. if not well... return false obv
+        if (self.cursor - self.limit_backward) < s.len() as i32
+            || !self
+                .current
+                .is_char_boundary(self.cursor as usize - s.len())
             || !self.current[self.cursor as usize - s.len()..].starts_with(s)
         {
             false
@@ -130,11 +129,11 @@ impl<'a> SnowballEnv<'a> {
             }
         }
         self.cursor = res;
-        return true;
+        true
     }
 
     pub fn hop_checked(&mut self, delta: i32) -> bool {
-        return delta >= 0 && self.hop(delta);
+        delta >= 0 && self.hop(delta)
     }
 
     pub fn hop_back(&mut self, mut delta: i32) -> bool {
@@ -150,11 +149,11 @@ impl<'a> SnowballEnv<'a> {
             }
         }
         self.cursor = res;
-        return true;
+        true
     }
 
     pub fn hop_back_checked(&mut self, delta: i32) -> bool {
-        return delta >= 0 && self.hop_back(delta);
+        delta >= 0 && self.hop_back(delta)
     }
 
     // A grouping is represented by a minimum code point, a maximum code point,
@@ -188,7 +187,7 @@ impl<'a> SnowballEnv<'a> {
             self.next_char();
             return true;
         }
-        return false;
+        false
     }
 
     pub fn in_grouping_b(&mut self, chars: &[u8], min: u32, max: u32) -> bool {
@@ -209,7 +208,7 @@ impl<'a> SnowballEnv<'a> {
             self.previous_char();
             return true;
         }
-        return false;
+        false
     }
 
     pub fn out_grouping(&mut self, chars: &[u8], min: u32, max: u32) -> bool {
@@ -228,7 +227,7 @@ impl<'a> SnowballEnv<'a> {
                 return true;
             }
         }
-        return false;
+        false
     }
 
     pub fn out_grouping_b(&mut self, chars: &[u8], min: u32, max: u32) -> bool {
@@ -249,7 +248,7 @@ impl<'a> SnowballEnv<'a> {
                 return true;
             }
         }
-        return false;
+        false
     }
 
     /// Helper function that removes the string slice between `bra` and `ket`
@@ -260,10 +259,10 @@ impl<'a> SnowballEnv<'a> {
     pub fn insert(&mut self, bra: i32, ket: i32, s: &str) {
         let adjustment = self.replace_s(bra, ket, s);
         if bra <= self.bra {
-            self.bra = self.bra + adjustment;
+            self.bra += adjustment;
         }
         if bra <= self.ket {
-            self.ket = self.ket + adjustment;
+            self.ket += adjustment;
         }
     }
 
@@ -275,6 +274,8 @@ impl<'a> SnowballEnv<'a> {
         self.current[self.bra as usize..self.ket as usize].to_string()
     }
 
+    // `common` grows inside the loop over the rest of the entry, which is intended
+    #[allow(clippy::mut_range_bound)]
     pub fn find_among<T>(&mut self, amongs: &[Among<T>], context: &mut T) -> i32 {
         use std::cmp::min;
         let mut i: i32 = 0;
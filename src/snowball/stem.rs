@@ -1,6 +1,6 @@
 use std::borrow::Cow;
 
-use super::algorithms::{porter, porter2};
+use super::algorithms::{french, german, porter, porter2, spanish};
 use super::SnowballEnv;
 use super::StemmingAlgorithm;
 use crate::lexer::Token;
@@ -18,6 +18,9 @@ pub fn stem(tok: Token, alg: StemmingAlgorithm) -> Option<Cow<str>> {
         match alg {
             StemmingAlgorithm::Porter => porter::stem(&mut env),
             StemmingAlgorithm::Porter2 => porter2::stem(&mut env),
+            StemmingAlgorithm::German => german::stem(&mut env),
+            StemmingAlgorithm::French => french::stem(&mut env),
+            StemmingAlgorithm::Spanish => spanish::stem(&mut env),
         };
 
         let stemmed_content = env.get_current();
@@ -27,3 +30,44 @@ pub fn stem(tok: Token, alg: StemmingAlgorithm) -> Option<Cow<str>> {
         None
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Checks a stemmer against a `word stem` per line vocabulary, sampled
+    /// from the Snowball project's own test data
+    fn check_vocabulary(alg: StemmingAlgorithm, vocabulary: &str) {
+        for line in vocabulary.lines() {
+            let (word, expected) = line.split_once(' ').unwrap();
+
+            let stemmed = stem(Token::Word(word), alg).unwrap();
+
+            assert_eq!(stemmed, expected, "{alg} stem of {word:?}");
+        }
+    }
+
+    #[test]
+    fn german_vocabulary() {
+        check_vocabulary(
+            StemmingAlgorithm::German,
+            include_str!("algorithms/vocabulary/german.txt"),
+        );
+    }
+
+    #[test]
+    fn french_vocabulary() {
+        check_vocabulary(
+            StemmingAlgorithm::French,
+            include_str!("algorithms/vocabulary/french.txt"),
+        );
+    }
+
+    #[test]
+    fn spanish_vocabulary() {
+        check_vocabulary(
+            StemmingAlgorithm::Spanish,
+            include_str!("algorithms/vocabulary/spanish.txt"),
+        );
+    }
+}
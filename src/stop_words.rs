@@ -0,0 +1,69 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// The Snowball English stop word list, matching the Porter2 stemmer
+/// https://snowballstem.org/algorithms/english/stop.txt
+pub const ENGLISH: &str = "\
+    i me my myself we our ours ourselves you your yours yourself yourselves he him \
+    his himself she her hers herself it its itself they them their theirs themselves \
+    what which who whom this that these those am is are was were be been being have \
+    has had having do does did doing would should could ought i'm you're he's she's \
+    it's we're they're i've you've we've they've i'd you'd he'd she'd we'd they'd \
+    i'll you'll he'll she'll we'll they'll isn't aren't wasn't weren't hasn't \
+    haven't hadn't doesn't don't didn't won't wouldn't shan't shouldn't can't cannot \
+    couldn't mustn't let's that's who's what's here's there's when's where's why's \
+    how's a an the and but if or because as until while of at by for with about \
+    against between into through during before after above below to from up down in \
+    out on off over under again further then once here there when where why how all \
+    any both each few more most other some such no nor not only own same so than too \
+    very\
+";
+
+/// Looks up a built-in, whitespace separated, stop word list by language name
+pub fn builtin(language: &str) -> Option<&'static str> {
+    match language.to_lowercase().as_str() {
+        "english" | "en" => Some(ENGLISH),
+        _ => None,
+    }
+}
+
+/// Reads a stop word list file. Words are separated by whitespace, and
+/// anything after a `|` or `#` on a line is a comment, as in Snowball's lists.
+pub fn from_file(path: &Path) -> io::Result<Vec<String>> {
+    let contents = fs::read_to_string(path)?;
+
+    Ok(contents
+        .lines()
+        .map(|line| line.split(['|', '#']).next().unwrap_or_default())
+        .flat_map(str::split_whitespace)
+        .map(str::to_string)
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_builtin() {
+        assert_eq!(builtin("English"), Some(ENGLISH));
+        assert_eq!(builtin("klingon"), None);
+    }
+
+    #[test]
+    fn test_from_file() {
+        let dir = tempdir().unwrap();
+        let file_path = dir.path().join("stop.txt");
+        fs::write(
+            &file_path,
+            "| a comment\nfoo bar  | trailing\n\n# another\nbaz\n",
+        )
+        .unwrap();
+
+        let words = from_file(&file_path).unwrap();
+
+        assert_eq!(words, vec!["foo", "bar", "baz"]);
+    }
+}